@@ -1,4 +1,3 @@
-use embedded_hal_bus::i2c::RefCellDevice;
 use ftdi_embedded_hal::libftd2xx::{self};
 use ftdi_embedded_hal::{self as hal};
 use rfof::{
@@ -20,18 +19,9 @@ fn main() {
     let frx_bus = RefCell::new(frx_hal.i2c().unwrap());
     let ftx_bus = RefCell::new(ftx_hal.i2c().unwrap());
 
-    let mut frx = Frx::new(
-        RefCellDevice::new(&frx_bus),
-        RefCellDevice::new(&frx_bus),
-        RefCellDevice::new(&frx_bus),
-    );
+    let mut frx = Frx::new_refcell(&frx_bus);
 
-    let mut ftx: Ftx<RefCellDevice<'_, ftdi_embedded_hal::I2c<libftd2xx::Ft4232ha>>> = Ftx::new(
-        RefCellDevice::new(&ftx_bus),
-        RefCellDevice::new(&ftx_bus),
-        RefCellDevice::new(&ftx_bus),
-        RefCellDevice::new(&ftx_bus),
-    );
+    let mut ftx = Ftx::new_refcell(&ftx_bus);
 
     ftx.init().unwrap();
     frx.init().unwrap();
@@ -40,7 +30,7 @@ fn main() {
     frx.atten.set(Attenuation::_15_25).unwrap();
     ftx.atten.set(Attenuation::_15_25).unwrap();
     ftx.adc.enable_lna(true).unwrap();
-    ftx.digipot.set(25.0).unwrap();
+    ftx.set_ld_current(25.0).unwrap();
 
     // Monitor example
     println!("---FTX---");
@@ -48,7 +38,7 @@ fn main() {
     println!("Unique ID: {:#x}", ftx.temp.uid().unwrap());
     println!("PD Current: {:.2} uA", ftx.adc.pd_current().unwrap());
     println!("LD Current: {:.2} mA", ftx.adc.ld_current().unwrap());
-    println!("LD Setpoint Current: {:.2} mA", ftx.digipot.get().unwrap());
+    println!("LD Setpoint Current: {:.2} mA", ftx.ld_setpoint().unwrap());
     println!("LNA Current: {:.2} mA", ftx.adc.lna_current().unwrap());
     println!("RF Power: {:.2} dBm", ftx.adc.rf_power().unwrap());
     println!("VDDA: {:.2} V", ftx.adc.analog_voltage().unwrap());
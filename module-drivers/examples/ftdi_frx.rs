@@ -1,4 +1,3 @@
-use embedded_hal_bus::i2c::RefCellDevice;
 use ftdi_embedded_hal::libftd2xx::{self};
 use ftdi_embedded_hal::{self as hal};
 use rfof::{modules::frx::Frx, peripherals::atten::Attenuation};
@@ -10,11 +9,7 @@ fn main() {
     let hal = hal::FtHal::init_freq(device, 100_000).unwrap();
     let bus = RefCell::new(hal.i2c().unwrap());
 
-    let mut frx = Frx::new(
-        RefCellDevice::new(&bus),
-        RefCellDevice::new(&bus),
-        RefCellDevice::new(&bus),
-    );
+    let mut frx = Frx::new_refcell(&bus);
 
     frx.init().unwrap();
 
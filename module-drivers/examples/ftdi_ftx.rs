@@ -1,4 +1,3 @@
-use embedded_hal_bus::i2c::RefCellDevice;
 use ftdi_embedded_hal::libftd2xx::{self};
 use ftdi_embedded_hal::{self as hal};
 use rfof::{modules::ftx::Ftx, peripherals::atten::Attenuation};
@@ -10,19 +9,14 @@ fn main() {
     let hal = hal::FtHal::init_freq(device, 100_000).unwrap(); // 100 kHz
     let bus = RefCell::new(hal.i2c().unwrap());
 
-    let mut ftx = Ftx::new(
-        RefCellDevice::new(&bus),
-        RefCellDevice::new(&bus),
-        RefCellDevice::new(&bus),
-        RefCellDevice::new(&bus),
-    );
+    let mut ftx = Ftx::new_refcell(&bus);
 
     ftx.init().unwrap();
 
     // Control example
     ftx.atten.set(Attenuation::_1_25).unwrap();
     ftx.adc.enable_lna(false).unwrap();
-    ftx.digipot.set(25.0).unwrap();
+    ftx.set_ld_current(25.0).unwrap();
 
     // Monitor example
     println!("Temperature: {:.2} C", ftx.temp.temp().unwrap());
@@ -30,7 +24,7 @@ fn main() {
     println!("Attenuation: {:#?}", ftx.atten.get().unwrap());
     println!("PD Current: {:.2} uA", ftx.adc.pd_current().unwrap());
     println!("LD Current: {:.2} mA", ftx.adc.ld_current().unwrap());
-    println!("LD Setpoint Current: {:.2} mA", ftx.digipot.get().unwrap());
+    println!("LD Setpoint Current: {:.2} mA", ftx.ld_setpoint().unwrap());
     println!("LNA Current: {:.2} mA", ftx.adc.lna_current().unwrap());
     println!("RF Power: {:.2} dBm", ftx.adc.rf_power().unwrap());
     println!("VDDA: {:.2} V", ftx.adc.analog_voltage().unwrap());
@@ -0,0 +1,28 @@
+use linux_embedded_hal::I2cdev;
+use rfof::{
+    bus::Tca9548a,
+    modules::{frx::Frx, ftx::Ftx},
+};
+
+fn main() {
+    // One chassis bus, with a TCA9548A (A2-A0 grounded) fanning it out to every module
+    let bus = I2cdev::new("/dev/i2c-1").unwrap();
+    let mux = Tca9548a::new(bus, 0);
+
+    let mut ftx = Ftx::new_shared(mux.channel(0).unwrap());
+    let mut frx = Frx::new_shared(mux.channel(1).unwrap());
+
+    ftx.init().unwrap();
+    frx.init().unwrap();
+
+    println!(
+        "FTX {:#x}: {:.2} C",
+        ftx.temp.uid().unwrap(),
+        ftx.temp.temp().unwrap()
+    );
+    println!(
+        "FRX {:#x}: {:.2} C",
+        frx.temp.uid().unwrap(),
+        frx.temp.temp().unwrap()
+    );
+}
@@ -0,0 +1,11 @@
+//! Wrappers around `I2c` buses that any module can be built on top of
+
+pub mod mux;
+pub mod retry;
+#[cfg(feature = "sim")]
+pub mod sim;
+
+pub use mux::{MuxChannel, Tca9548a};
+pub use retry::{RetryConfig, RetryI2c, RetryStats};
+#[cfg(feature = "sim")]
+pub use sim::{SimBus, SimMux};
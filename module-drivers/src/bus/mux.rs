@@ -0,0 +1,146 @@
+//! TCA9548A/PCA9548 I2C multiplexer, for several modules on one bus
+//!
+//! Every module uses the same fixed addresses, so each one needs its own downstream channel of a
+//! mux. [`MuxChannel`] is a virtual bus for one channel that selects it before every transaction
+//! (only when another channel was selected last), and is cheap to copy so a whole module can be
+//! built on one with `new_shared`/`attach_shared`:
+//!
+//! ```ignore
+//! let mux = Tca9548a::new(bus, 0);
+//! let mut ftx = Ftx::new_shared(mux.channel(0).unwrap());
+//! let mut frx = Frx::new_shared(mux.channel(1).unwrap());
+//! ```
+
+use core::cell::RefCell;
+use embedded_hal::i2c::{ErrorType, I2c, Operation, SevenBitAddress};
+
+/// Address with A2-A0 tied to ground
+const ADDR_BASE: u8 = 0x70;
+
+/// Number of downstream channels
+pub const CHANNELS: u8 = 8;
+
+struct Inner<I2C> {
+    bus: I2C,
+    addr: u8,
+    /// Channel the mux was last switched to, `None` if unknown
+    selected: Option<u8>,
+}
+
+impl<I2C: I2c> Inner<I2C> {
+    fn write_control(&mut self, control: u8) -> Result<(), I2C::Error> {
+        // Forget the selection first, so a failed write makes the next transaction try again
+        self.selected = None;
+        self.bus.write(self.addr, &[control])
+    }
+
+    fn select(&mut self, channel: u8) -> Result<(), I2C::Error> {
+        if self.selected != Some(channel) {
+            self.write_control(1 << channel)?;
+            self.selected = Some(channel);
+        }
+        Ok(())
+    }
+}
+
+/// 8-channel I2C multiplexer, owning the upstream bus
+pub struct Tca9548a<I2C> {
+    inner: RefCell<Inner<I2C>>,
+}
+
+impl<I2C, E> Tca9548a<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// `a` is the state of the A2-A0 address pins (0-7)
+    pub fn new(bus: I2C, a: u8) -> Self {
+        Self {
+            inner: RefCell::new(Inner {
+                bus,
+                addr: ADDR_BASE | (a & 0b111),
+                selected: None,
+            }),
+        }
+    }
+
+    /// Virtual bus for one downstream channel, `None` if there is no such channel
+    pub fn channel(&self, channel: u8) -> Option<MuxChannel<'_, I2C>> {
+        (channel < CHANNELS).then_some(MuxChannel {
+            inner: &self.inner,
+            channel,
+        })
+    }
+
+    /// Virtual buses for every downstream channel
+    pub fn channels(&self) -> [MuxChannel<'_, I2C>; CHANNELS as usize] {
+        core::array::from_fn(|channel| MuxChannel {
+            inner: &self.inner,
+            channel: channel as u8,
+        })
+    }
+
+    /// Disconnect every downstream channel
+    pub fn disconnect(&self) -> Result<(), E> {
+        self.inner.borrow_mut().write_control(0)
+    }
+
+    /// Read back the control register, one bit per connected channel
+    pub fn connected(&self) -> Result<u8, E> {
+        let mut inner = self.inner.borrow_mut();
+        let mut control = [0u8; 1];
+        let addr = inner.addr;
+        inner.bus.read(addr, &mut control)?;
+        Ok(control[0])
+    }
+
+    /// Forget which channel is selected, so the next transaction selects it again
+    ///
+    /// Needed if something else (a reset, another bus master) may have switched the mux.
+    pub fn invalidate(&self) {
+        self.inner.borrow_mut().selected = None;
+    }
+
+    pub fn into_inner(self) -> I2C {
+        self.inner.into_inner().bus
+    }
+}
+
+/// One downstream channel of a [`Tca9548a`], switched to automatically
+///
+/// A failed channel switch is returned as the error of the transaction it was for.
+pub struct MuxChannel<'a, I2C> {
+    inner: &'a RefCell<Inner<I2C>>,
+    channel: u8,
+}
+
+impl<I2C> MuxChannel<'_, I2C> {
+    /// Which downstream channel this is
+    pub fn index(&self) -> u8 {
+        self.channel
+    }
+}
+
+impl<I2C> Clone for MuxChannel<'_, I2C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<I2C> Copy for MuxChannel<'_, I2C> {}
+
+impl<I2C: I2c> ErrorType for MuxChannel<'_, I2C> {
+    type Error = I2C::Error;
+}
+
+impl<I2C: I2c> I2c for MuxChannel<'_, I2C> {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut inner = self.inner.borrow_mut();
+        inner.select(self.channel)?;
+        inner.bus.transaction(address, operations)
+    }
+}
@@ -0,0 +1,172 @@
+//! Retrying failed transactions on marginal buses
+//!
+//! Long cables see the odd NACK or lost arbitration that goes away when the transaction is
+//! simply repeated, so those are retried. A retry isn't free of side effects though: the chip
+//! may already have acted on part of the failed attempt, and some reads change state (reading
+//! the TMP117 configuration register clears its flags). Our drivers only issue whole register
+//! accesses that they can live with repeating, but a generic bus error says nothing about what
+//! reached the chip, so it is never retried.
+
+use embedded_hal::{
+    delay::DelayNs,
+    i2c::{Error as _, ErrorKind, ErrorType, I2c, Operation, SevenBitAddress},
+};
+
+/// How hard to try before giving up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// Extra attempts after the first one fails
+    pub retries: u8,
+    /// Wait (in us) before the first retry
+    pub backoff_us: u32,
+    /// Factor the wait grows by after every retry
+    pub backoff_factor: u32,
+}
+
+impl Default for RetryConfig {
+    /// Three retries, waiting 100, 200 and 400 us
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            backoff_us: 100,
+            backoff_factor: 2,
+        }
+    }
+}
+
+/// Counters of what the retries have been doing
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetryStats {
+    /// Transactions issued by the driver on top
+    pub transactions: u32,
+    /// Retries made, across every transaction
+    pub retries: u32,
+    /// Transactions that failed at first but succeeded on a retry
+    pub recovered: u32,
+    /// Transactions that failed for good (out of retries or not retryable)
+    pub failures: u32,
+}
+
+/// Whether an error is transient and worth repeating the transaction for
+pub fn is_retryable(kind: ErrorKind) -> bool {
+    matches!(
+        kind,
+        ErrorKind::NoAcknowledge(_) | ErrorKind::ArbitrationLoss
+    )
+}
+
+/// An `I2c` bus that retries failed transactions with exponential backoff
+pub struct RetryI2c<I2C, D> {
+    bus: I2C,
+    delay: D,
+    config: RetryConfig,
+    stats: RetryStats,
+}
+
+impl<I2C, D> RetryI2c<I2C, D>
+where
+    I2C: I2c,
+    D: DelayNs,
+{
+    /// Wrap a bus with the default retry settings
+    pub fn new(bus: I2C, delay: D) -> Self {
+        Self::with_config(bus, delay, RetryConfig::default())
+    }
+
+    pub fn with_config(bus: I2C, delay: D, config: RetryConfig) -> Self {
+        Self {
+            bus,
+            delay,
+            config,
+            stats: RetryStats::default(),
+        }
+    }
+
+    pub fn config(&self) -> RetryConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: RetryConfig) {
+        self.config = config;
+    }
+
+    pub fn stats(&self) -> RetryStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = RetryStats::default();
+    }
+
+    /// Give back the underlying bus and delay
+    pub fn into_inner(self) -> (I2C, D) {
+        (self.bus, self.delay)
+    }
+
+    fn retry<T>(
+        &mut self,
+        mut f: impl FnMut(&mut I2C) -> Result<T, I2C::Error>,
+    ) -> Result<T, I2C::Error> {
+        self.stats.transactions = self.stats.transactions.wrapping_add(1);
+        let mut backoff = self.config.backoff_us;
+        let mut attempt = 0;
+        loop {
+            match f(&mut self.bus) {
+                Ok(v) => {
+                    if attempt > 0 {
+                        self.stats.recovered = self.stats.recovered.wrapping_add(1);
+                    }
+                    return Ok(v);
+                }
+                Err(e) if attempt < self.config.retries && is_retryable(e.kind()) => {
+                    attempt += 1;
+                    self.stats.retries = self.stats.retries.wrapping_add(1);
+                    self.delay.delay_us(backoff);
+                    backoff = backoff.saturating_mul(self.config.backoff_factor);
+                }
+                Err(e) => {
+                    self.stats.failures = self.stats.failures.wrapping_add(1);
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+impl<I2C, D> ErrorType for RetryI2c<I2C, D>
+where
+    I2C: ErrorType,
+{
+    type Error = I2C::Error;
+}
+
+impl<I2C, D> I2c for RetryI2c<I2C, D>
+where
+    I2C: I2c,
+    D: DelayNs,
+{
+    fn read(&mut self, address: SevenBitAddress, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.retry(|bus| bus.read(address, read))
+    }
+
+    fn write(&mut self, address: SevenBitAddress, write: &[u8]) -> Result<(), Self::Error> {
+        self.retry(|bus| bus.write(address, write))
+    }
+
+    fn write_read(
+        &mut self,
+        address: SevenBitAddress,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.retry(|bus| bus.write_read(address, write, read))
+    }
+
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.retry(|bus| bus.transaction(address, operations))
+    }
+}
@@ -0,0 +1,942 @@
+//! Simulated module bus, for running the drivers without hardware
+//!
+//! [`SimBus`] answers at the real addresses of the four chips with register-level models of
+//! each ([`Tmp117`], [`Tla2528`], [`Tca6408a`], [`Cat5171`]), wired together through a
+//! behavioural [`Board`]: the digipot sets the laser current, the laser lights the monitor
+//! photodiode past threshold, the LNA draws current while its enable pin is driven and the RF
+//! detector sees the input power less the attenuation. The ADC converts whatever the board
+//! presents at its pins, through the same shunts and gains the drivers assume.
+//!
+//! ```ignore
+//! let bus = RefCell::new(SimBus::ftx());
+//! let mut ftx = Ftx::new_refcell(&bus);
+//! ftx.init()?;
+//! bus.borrow_mut().tmp117.temp_c = 80.0;
+//! ```
+//!
+//! [`SimMux`] puts several of them behind a simulated TCA9548A. Only built with the `sim` feature.
+
+use crate::{
+    modules::probe::ModuleKind,
+    peripherals::{adc, Chip},
+};
+use core::fmt;
+use embedded_hal::{
+    delay::DelayNs,
+    i2c::{ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation, SevenBitAddress},
+};
+
+/// Error returned by the simulated buses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimError(pub ErrorKind);
+
+impl embedded_hal::i2c::Error for SimError {
+    fn kind(&self) -> ErrorKind {
+        self.0
+    }
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SimError {}
+
+const NACK: SimError = SimError(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+
+/// Byte-level behaviour of a chip on the bus
+trait Device {
+    /// One byte of a write, `index` counting from the start of the (contiguous) write.
+    /// Returns whether this committed a register write.
+    fn write(&mut self, byte: u8, index: usize) -> bool;
+    /// One byte of a read, `index` counting from the start of the (contiguous) read
+    fn read(&mut self, index: usize) -> u8;
+}
+
+// ---- TMP117
+
+/// Temperature (in C) of one LSB of the TMP117 registers
+const TMP117_LSB: f32 = 7.8125e-3;
+
+/// Configuration register bits
+const CFG_HIGH_ALERT: u16 = 1 << 15;
+const CFG_LOW_ALERT: u16 = 1 << 14;
+const CFG_DATA_READY: u16 = 1 << 13;
+const CFG_EEPROM_BUSY: u16 = 1 << 12;
+const CFG_MODE_SHIFT: u16 = 10;
+const CFG_THERM: u16 = 1 << 4;
+const CFG_SOFT_RESET: u16 = 1 << 1;
+/// Bits a write to the configuration register can change
+const CFG_WRITABLE: u16 = 0x0FFC;
+
+/// Number of busy flag reads an EEPROM programming cycle lasts
+const EEPROM_BUSY_READS: u8 = 2;
+
+/// Contents of the TMP117 EEPROM, loaded into the registers on reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tmp117Eeprom {
+    config: u16,
+    high_limit: i16,
+    low_limit: i16,
+    offset: i16,
+    general: [u16; 3],
+}
+
+/// TMP117 temperature sensor (and the module's unique ID)
+///
+/// Conversions are instantaneous: in continuous mode every read of the result register converts
+/// `temp_c`, a one-shot converts when it's triggered. The EEPROM stays busy for a couple of
+/// polls after every programming write.
+#[derive(Debug, Clone)]
+pub struct Tmp117 {
+    /// Temperature the sensor sees (in C)
+    pub temp_c: f32,
+    /// DEVICE_ID register, 0x0117 for a real TMP117
+    pub device_id: u16,
+    /// The converter has hung, no conversion ever completes
+    pub stalled: bool,
+    eeprom: Tmp117Eeprom,
+    pointer: u8,
+    msb: u8,
+    latched: u16,
+    config: u16,
+    high_limit: i16,
+    low_limit: i16,
+    offset: i16,
+    general: [u16; 3],
+    result: i16,
+    unlocked: bool,
+    busy_reads: u8,
+}
+
+impl Tmp117 {
+    /// A sensor at 25 C, with the UID split across the general-purpose EEPROM words
+    pub fn new(uid: u64) -> Self {
+        let eeprom = Tmp117Eeprom {
+            config: 0x0220,
+            high_limit: 0x6000,
+            low_limit: i16::MIN,
+            offset: 0,
+            general: [(uid >> 32) as u16, (uid >> 16) as u16, uid as u16],
+        };
+        let mut sensor = Self {
+            temp_c: 25.0,
+            device_id: 0x0117,
+            stalled: false,
+            eeprom,
+            pointer: 0,
+            msb: 0,
+            latched: 0,
+            config: 0,
+            high_limit: 0,
+            low_limit: 0,
+            offset: 0,
+            general: [0; 3],
+            result: 0,
+            unlocked: false,
+            busy_reads: 0,
+        };
+        sensor.reset();
+        sensor
+    }
+
+    /// The unique ID, as the driver assembles it from the EEPROM
+    pub fn uid(&self) -> u64 {
+        let [one, two, three] = self.eeprom.general.map(u64::from);
+        (one << 32) | (two << 16) | three
+    }
+
+    /// Whether the EEPROM is unlocked for programming
+    pub fn is_unlocked(&self) -> bool {
+        self.unlocked
+    }
+
+    /// Power-on reset, reloading the registers from EEPROM
+    pub fn reset(&mut self) {
+        self.config = self.eeprom.config;
+        self.high_limit = self.eeprom.high_limit;
+        self.low_limit = self.eeprom.low_limit;
+        self.offset = self.eeprom.offset;
+        self.general = self.eeprom.general;
+        self.result = i16::MIN;
+        self.unlocked = false;
+        self.busy_reads = 0;
+    }
+
+    fn mode(&self) -> u16 {
+        (self.config >> CFG_MODE_SHIFT) & 0b11
+    }
+
+    fn convert(&mut self) {
+        if self.stalled {
+            return;
+        }
+        let raw = (self.temp_c / TMP117_LSB) as i32 + self.offset as i32;
+        self.result = raw.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        self.config |= CFG_DATA_READY;
+        if self.config & CFG_THERM != 0 {
+            // Hysteresis between the limits, the low flag is unused
+            if self.result > self.high_limit {
+                self.config |= CFG_HIGH_ALERT;
+            } else if self.result < self.low_limit {
+                self.config &= !CFG_HIGH_ALERT;
+            }
+        } else {
+            if self.result >= self.high_limit {
+                self.config |= CFG_HIGH_ALERT;
+            }
+            if self.result <= self.low_limit {
+                self.config |= CFG_LOW_ALERT;
+            }
+        }
+    }
+
+    fn read_reg(&mut self, reg: u8) -> u16 {
+        // Free-running, a conversion has always completed since the last look at the results
+        if matches!(reg, 0x00 | 0x01) && matches!(self.mode(), 0b00 | 0b10) {
+            self.convert();
+        }
+        match reg {
+            0x00 => {
+                self.config &= !CFG_DATA_READY;
+                self.result as u16
+            }
+            0x01 => {
+                let busy = if self.busy_reads > 0 {
+                    self.busy_reads -= 1;
+                    CFG_EEPROM_BUSY
+                } else {
+                    0
+                };
+                let value = self.config | busy;
+                // Reading clears data ready, and the flags latched in alert mode
+                self.config &= !CFG_DATA_READY;
+                if self.config & CFG_THERM == 0 {
+                    self.config &= !(CFG_HIGH_ALERT | CFG_LOW_ALERT);
+                }
+                value
+            }
+            0x02 => self.high_limit as u16,
+            0x03 => self.low_limit as u16,
+            0x04 => ((self.unlocked as u16) << 15) | ((self.busy_reads > 0) as u16) << 14,
+            0x05 => self.general[0],
+            0x06 => self.general[1],
+            0x07 => self.offset as u16,
+            0x08 => self.general[2],
+            0x0F => self.device_id,
+            _ => 0,
+        }
+    }
+
+    fn write_reg(&mut self, reg: u8, value: u16) {
+        // With the EEPROM unlocked, writes to the EEPROM-backed registers also program it
+        let program = self.unlocked && matches!(reg, 0x01..=0x03 | 0x05..=0x08);
+        match reg {
+            0x01 => {
+                if value & CFG_SOFT_RESET != 0 {
+                    self.reset();
+                    return;
+                }
+                self.config = (self.config & !CFG_WRITABLE) | (value & CFG_WRITABLE);
+                if program {
+                    self.eeprom.config = self.config & CFG_WRITABLE;
+                }
+                if self.mode() == 0b11 {
+                    // One-shot converts once, then drops back to shutdown
+                    self.convert();
+                    self.config =
+                        (self.config & !(0b11 << CFG_MODE_SHIFT)) | (0b01 << CFG_MODE_SHIFT);
+                }
+            }
+            0x02 => {
+                self.high_limit = value as i16;
+                if program {
+                    self.eeprom.high_limit = self.high_limit;
+                }
+            }
+            0x03 => {
+                self.low_limit = value as i16;
+                if program {
+                    self.eeprom.low_limit = self.low_limit;
+                }
+            }
+            0x04 => self.unlocked = value & (1 << 15) != 0,
+            // The general-purpose words only change by programming the EEPROM
+            0x05 | 0x06 | 0x08 if program => {
+                let word = match reg {
+                    0x05 => 0,
+                    0x06 => 1,
+                    _ => 2,
+                };
+                self.general[word] = value;
+                self.eeprom.general[word] = value;
+            }
+            0x07 => {
+                self.offset = value as i16;
+                if program {
+                    self.eeprom.offset = self.offset;
+                }
+            }
+            _ => (),
+        }
+        if program {
+            self.busy_reads = EEPROM_BUSY_READS;
+        }
+    }
+}
+
+impl Device for Tmp117 {
+    fn write(&mut self, byte: u8, index: usize) -> bool {
+        match index {
+            0 => self.pointer = byte,
+            1 => self.msb = byte,
+            2 => {
+                self.write_reg(self.pointer, u16::from_be_bytes([self.msb, byte]));
+                return true;
+            }
+            _ => (),
+        }
+        false
+    }
+
+    fn read(&mut self, index: usize) -> u8 {
+        // Registers are 16 bits, MSB first
+        let byte = index % 2;
+        if byte == 0 {
+            self.latched = self.read_reg(self.pointer);
+        }
+        self.latched.to_be_bytes()[byte]
+    }
+}
+
+// ---- TLA2528
+
+const TLA_SINGLE_READ: u8 = 0x10;
+const TLA_SINGLE_WRITE: u8 = 0x08;
+const TLA_SET_BIT: u8 = 0x18;
+const TLA_CLEAR_BIT: u8 = 0x20;
+
+const TLA_SYSTEM_STATUS: usize = 0x00;
+const TLA_GENERAL_CFG: usize = 0x01;
+const TLA_PIN_CFG: usize = 0x05;
+const TLA_GPIO_CFG: usize = 0x07;
+const TLA_GPO_DRIVE_CFG: usize = 0x09;
+const TLA_GPO_VALUE: usize = 0x0B;
+const TLA_CHANNEL_SEL: usize = 0x11;
+
+/// Size of the modelled register space
+const TLA_REGS: usize = 0x20;
+
+/// Analog reference of the ADC on both modules (in V)
+const ADC_VREF: f32 = 5.0;
+
+/// TLA2528 8-channel ADC / GPIO in manual conversion mode
+///
+/// `inputs` holds the voltage on each pin, every two bytes read outside a register read are a
+/// conversion of the channel in CHANNEL_SEL.
+#[derive(Debug, Clone)]
+pub struct Tla2528 {
+    /// Voltage at each analog input (in V)
+    pub inputs: [f32; 8],
+    regs: [u8; TLA_REGS],
+    opcode: u8,
+    reg: usize,
+    pending_read: Option<usize>,
+    reading: Option<usize>,
+    code: u16,
+}
+
+impl Default for Tla2528 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tla2528 {
+    pub fn new() -> Self {
+        let mut adc = Self {
+            inputs: [0.0; 8],
+            regs: [0; TLA_REGS],
+            opcode: 0,
+            reg: 0,
+            pending_read: None,
+            reading: None,
+            code: 0,
+        };
+        adc.reset();
+        adc
+    }
+
+    /// Power-on reset, with the brown-out flag set
+    pub fn reset(&mut self) {
+        self.regs = [0; TLA_REGS];
+        self.regs[TLA_SYSTEM_STATUS] = 0x81;
+    }
+
+    /// Raw register contents
+    pub fn reg(&self, reg: u8) -> u8 {
+        self.regs.get(reg as usize).copied().unwrap_or(0)
+    }
+
+    /// Whether a pin is a GPIO driven high as a push-pull output
+    pub fn output(&self, chan: u8) -> bool {
+        let bit = |reg: usize| self.regs[reg] & (1 << chan) != 0;
+        bit(TLA_PIN_CFG) && bit(TLA_GPIO_CFG) && bit(TLA_GPO_DRIVE_CFG) && bit(TLA_GPO_VALUE)
+    }
+
+    /// Whether a pin is configured as an analog input
+    pub fn is_analog(&self, chan: u8) -> bool {
+        self.regs[TLA_PIN_CFG] & (1 << chan) == 0
+    }
+
+    fn write_reg(&mut self, reg: usize, value: u8) {
+        if reg >= TLA_REGS {
+            return;
+        }
+        match reg {
+            // BOR is write-one-to-clear, the rest is read-only
+            TLA_SYSTEM_STATUS => self.regs[reg] &= !(value & 1),
+            TLA_GENERAL_CFG => {
+                // RST and CAL are self-clearing, CAL is instantaneous here
+                if value & 1 != 0 {
+                    self.reset();
+                } else {
+                    self.regs[reg] = value & !0b11;
+                }
+            }
+            _ => self.regs[reg] = value,
+        }
+    }
+
+    fn convert(&self) -> u16 {
+        let chan = (self.regs[TLA_CHANNEL_SEL] & 0x7) as usize;
+        if !self.is_analog(chan as u8) {
+            return 0;
+        }
+        let code = (self.inputs[chan] / ADC_VREF * 4095.0 + 0.5).clamp(0.0, 4095.0) as u16;
+        code << 4
+    }
+}
+
+impl Device for Tla2528 {
+    fn write(&mut self, byte: u8, index: usize) -> bool {
+        match index {
+            0 => {
+                self.opcode = byte;
+                self.pending_read = None;
+                false
+            }
+            1 => {
+                self.reg = byte as usize;
+                if self.opcode == TLA_SINGLE_READ {
+                    self.pending_read = Some(self.reg);
+                }
+                false
+            }
+            _ => {
+                let old = self.regs.get(self.reg).copied().unwrap_or(0);
+                let value = match self.opcode {
+                    TLA_SINGLE_WRITE => byte,
+                    TLA_SET_BIT => old | byte,
+                    TLA_CLEAR_BIT => old & !byte,
+                    _ => return false,
+                };
+                self.write_reg(self.reg, value);
+                // Continuous writes go to successive registers
+                self.reg += 1;
+                true
+            }
+        }
+    }
+
+    fn read(&mut self, index: usize) -> u8 {
+        if index == 0 {
+            self.reading = self.pending_read.take();
+        }
+        match self.reading {
+            Some(reg) => self.regs.get(reg + index).copied().unwrap_or(0),
+            None => {
+                // Every two bytes are a new conversion, MSB first
+                let byte = index % 2;
+                if byte == 0 {
+                    self.code = self.convert();
+                }
+                self.code.to_be_bytes()[byte]
+            }
+        }
+    }
+}
+
+// ---- TCA6408A
+
+/// TCA6408A 8-bit I/O expander, driving the F1958 attenuator
+///
+/// The attenuator follows D6-D0 while its latch enable (P7) is driven high, and holds the last
+/// value otherwise. It powers up at maximum attenuation.
+#[derive(Debug, Clone)]
+pub struct Tca6408a {
+    output: u8,
+    polarity: u8,
+    config: u8,
+    pointer: u8,
+    latched: u8,
+}
+
+impl Default for Tca6408a {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tca6408a {
+    pub fn new() -> Self {
+        Self {
+            output: 0xFF,
+            polarity: 0,
+            config: 0xFF,
+            pointer: 0,
+            latched: 0x7F,
+        }
+    }
+
+    /// Output port register
+    pub fn output(&self) -> u8 {
+        self.output
+    }
+
+    /// Configuration register (a set bit is an input)
+    pub fn config(&self) -> u8 {
+        self.config
+    }
+
+    /// Attenuation (in dB) the F1958 is latched to
+    pub fn attenuation_db(&self) -> f32 {
+        self.latched as f32 * 0.25
+    }
+
+    fn update_latch(&mut self) {
+        // Undriven pins float high through the pull-ups
+        let pins = self.output | self.config;
+        if pins & 0x80 != 0 && self.config & 0x7F == 0 {
+            self.latched = pins & 0x7F;
+        }
+    }
+}
+
+impl Device for Tca6408a {
+    fn write(&mut self, byte: u8, index: usize) -> bool {
+        match (index, self.pointer) {
+            (0, _) => {
+                self.pointer = byte & 0x3;
+                return false;
+            }
+            // The input port is read-only
+            (_, 0) => return false,
+            (_, 1) => self.output = byte,
+            (_, 2) => self.polarity = byte,
+            (_, _) => self.config = byte,
+        }
+        self.update_latch();
+        true
+    }
+
+    fn read(&mut self, _index: usize) -> u8 {
+        match self.pointer {
+            0 => (self.output & !self.config | self.config) ^ self.polarity,
+            1 => self.output,
+            2 => self.polarity,
+            _ => self.config,
+        }
+    }
+}
+
+// ---- CAT5171
+
+/// CAT5171 256-position digipot setting the laser current source
+///
+/// Powers up (and resets) to midscale, like the real part.
+#[derive(Debug, Clone)]
+pub struct Cat5171 {
+    /// Wiper position
+    pub wiper: u8,
+    /// Whether the shutdown bit is set (opening the current source)
+    pub shutdown: bool,
+}
+
+impl Default for Cat5171 {
+    fn default() -> Self {
+        Self {
+            wiper: 0x80,
+            shutdown: false,
+        }
+    }
+}
+
+impl Device for Cat5171 {
+    fn write(&mut self, byte: u8, index: usize) -> bool {
+        match index {
+            0 => {
+                if byte & (1 << 6) != 0 {
+                    self.wiper = 0x80;
+                }
+                self.shutdown = byte & (1 << 5) != 0;
+                false
+            }
+            1 => {
+                self.wiper = byte;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn read(&mut self, _index: usize) -> u8 {
+        self.wiper
+    }
+}
+
+// ---- The board
+
+/// Analog behaviour of the module around the chips
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Board {
+    /// Which module this is, setting the ADC channel map
+    pub kind: ModuleKind,
+    /// Digital supply (in V)
+    pub vdd: f32,
+    /// Analog supply (in V)
+    pub vdda: f32,
+    /// LNA bias voltage while enabled (in V)
+    pub lna_voltage: f32,
+    /// LNA bias current while enabled (in mA)
+    pub lna_current: f32,
+    /// Laser current at full scale of the digipot (in mA)
+    pub ld_full_scale: f32,
+    /// Laser threshold current (in mA)
+    pub threshold: f32,
+    /// Monitor photodiode current per laser current above threshold (in uA/mA)
+    pub slope: f32,
+    /// FRX received photodiode current (in mA)
+    pub rx_pd_current: f32,
+    /// RF power ahead of the attenuator (in dBm)
+    pub rf_input: f32,
+}
+
+impl Board {
+    /// A healthy FTX
+    pub fn ftx() -> Self {
+        Self {
+            kind: ModuleKind::Ftx,
+            vdd: 5.0,
+            vdda: 5.0,
+            lna_voltage: 5.0,
+            lna_current: 60.0,
+            ld_full_scale: 50.0,
+            threshold: 8.0,
+            slope: 12.0,
+            rx_pd_current: 0.0,
+            rf_input: -10.0,
+        }
+    }
+
+    /// A healthy FRX
+    pub fn frx() -> Self {
+        Self {
+            kind: ModuleKind::Frx,
+            rx_pd_current: 2.0,
+            ..Self::ftx()
+        }
+    }
+}
+
+/// Voltage the RF detector puts out for a power (in dBm), the inverse of the driver's fit
+fn detector_voltage(dbm: f32) -> f32 {
+    (dbm + 55.0) / 17.74
+}
+
+/// A whole FTX or FRX on a simulated bus
+///
+/// The chip models are public to inspect and poke at. Removed chips NACK, and
+/// [`fail_next`](Self::fail_next) makes the next transactions fail without reaching a chip.
+#[derive(Debug, Clone)]
+pub struct SimBus {
+    pub board: Board,
+    pub tmp117: Tmp117,
+    pub tla2528: Tla2528,
+    pub tca6408a: Tca6408a,
+    pub cat5171: Cat5171,
+    /// Transactions issued, including failed ones
+    pub transactions: u32,
+    /// Writes that changed a register on any chip
+    pub register_writes: u32,
+    absent: [bool; 4],
+    failures: u32,
+    failure_kind: ErrorKind,
+}
+
+impl SimBus {
+    /// An FTX with a default UID
+    pub fn ftx() -> Self {
+        Self::new(Board::ftx(), 0x0000_0117_5A17_F7A0)
+    }
+
+    /// An FRX (no digipot) with a default UID
+    pub fn frx() -> Self {
+        let mut bus = Self::new(Board::frx(), 0x0000_0117_5A17_F7B0);
+        bus.remove(Chip::Cat5171);
+        bus
+    }
+
+    pub fn new(board: Board, uid: u64) -> Self {
+        let mut bus = Self {
+            board,
+            tmp117: Tmp117::new(uid),
+            tla2528: Tla2528::new(),
+            tca6408a: Tca6408a::new(),
+            cat5171: Cat5171::default(),
+            transactions: 0,
+            register_writes: 0,
+            absent: [false; 4],
+            failures: 0,
+            failure_kind: ErrorKind::Other,
+        };
+        bus.update_inputs();
+        bus
+    }
+
+    /// Change the unique ID reported by the temperature sensor
+    pub fn with_uid(mut self, uid: u64) -> Self {
+        self.tmp117 = Tmp117::new(uid);
+        self
+    }
+
+    fn slot(chip: Chip) -> usize {
+        Chip::ALL.iter().position(|c| *c == chip).unwrap()
+    }
+
+    /// Take a chip off the bus, so it NACKs
+    pub fn remove(&mut self, chip: Chip) {
+        self.absent[Self::slot(chip)] = true;
+    }
+
+    /// Put a removed chip back
+    pub fn restore(&mut self, chip: Chip) {
+        self.absent[Self::slot(chip)] = false;
+    }
+
+    /// Whether a chip answers
+    pub fn present(&self, chip: Chip) -> bool {
+        !self.absent[Self::slot(chip)]
+    }
+
+    /// Fail the next `count` transactions with `kind`
+    pub fn fail_next(&mut self, count: u32, kind: ErrorKind) {
+        self.failures = count;
+        self.failure_kind = kind;
+    }
+
+    /// Whether the LNA enable pin is driven high (FTX)
+    pub fn lna_enabled(&self) -> bool {
+        self.board.kind == ModuleKind::Ftx && self.tla2528.output(adc::ftx::LNA_EN)
+    }
+
+    /// Laser current the current source is putting out (in mA)
+    pub fn ld_current(&self) -> f32 {
+        if self.board.kind != ModuleKind::Ftx || !self.present(Chip::Cat5171) {
+            return 0.0;
+        }
+        if self.cat5171.shutdown {
+            return 0.0;
+        }
+        self.cat5171.wiper as f32 * self.board.ld_full_scale / 255.0
+    }
+
+    /// Monitor photodiode current (in uA)
+    pub fn pd_current(&self) -> f32 {
+        (self.ld_current() - self.board.threshold).max(0.0) * self.board.slope
+    }
+
+    /// RF power at the detector (in dBm)
+    pub fn rf_power(&self) -> f32 {
+        self.board.rf_input - self.tca6408a.attenuation_db()
+    }
+
+    /// Present the board's analog values at the ADC pins
+    fn update_inputs(&mut self) {
+        let b = self.board;
+        let rf = detector_voltage(self.rf_power());
+        let ld = self.ld_current();
+        let pd = self.pd_current();
+        let mut inputs = self.tla2528.inputs;
+        match b.kind {
+            ModuleKind::Ftx => {
+                use adc::ftx::*;
+                let lna = self.lna_enabled();
+                inputs[VDDA as usize] = b.vdda * VDDA_GAIN;
+                inputs[PDI as usize] = pd * 1e-6 * PDI_GAIN * PDI_SHUNT;
+                inputs[RF as usize] = rf;
+                inputs[LNAI as usize] = if lna {
+                    b.lna_current * 1e-3 * LNAI_GAIN * LNAI_SHUNT
+                } else {
+                    0.0
+                };
+                inputs[LDI as usize] = ld * 1e-3 * LDI_GAIN * LDI_SHUNT;
+                inputs[VLNA as usize] = if lna { b.lna_voltage * VLNA_GAIN } else { 0.0 };
+                inputs[VDD as usize] = b.vdd * VDD_GAIN;
+            }
+            ModuleKind::Frx => {
+                use adc::frx::*;
+                inputs[RF as usize] = rf;
+                inputs[PDI as usize] = b.rx_pd_current * 1e-3 * GAIN * PDI_SHUNT;
+            }
+            ModuleKind::Unknown => (),
+        }
+        self.tla2528.inputs = inputs;
+    }
+
+    fn device(&mut self, address: u8) -> Option<&mut dyn Device> {
+        let chip = *Chip::ALL.iter().find(|c| c.addr() == address)?;
+        if !self.present(chip) {
+            return None;
+        }
+        Some(match chip {
+            Chip::Tmp117 => &mut self.tmp117,
+            Chip::Tla2528 => &mut self.tla2528,
+            Chip::Tca6408a => &mut self.tca6408a,
+            Chip::Cat5171 => &mut self.cat5171,
+        })
+    }
+}
+
+impl ErrorType for SimBus {
+    type Error = SimError;
+}
+
+impl I2c for SimBus {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transactions += 1;
+        if self.failures > 0 {
+            self.failures -= 1;
+            return Err(SimError(self.failure_kind));
+        }
+        self.update_inputs();
+        let device = self.device(address).ok_or(NACK)?;
+        let mut writes = 0;
+        // Adjacent operations of the same kind are one continuous transfer on the wire
+        let mut index = 0;
+        let mut last_was_write = None;
+        for op in operations {
+            match op {
+                Operation::Write(bytes) => {
+                    if last_was_write != Some(true) {
+                        index = 0;
+                    }
+                    for byte in bytes.iter() {
+                        writes += device.write(*byte, index) as u32;
+                        index += 1;
+                    }
+                    last_was_write = Some(true);
+                }
+                Operation::Read(buf) => {
+                    if last_was_write != Some(false) {
+                        index = 0;
+                    }
+                    for byte in buf.iter_mut() {
+                        *byte = device.read(index);
+                        index += 1;
+                    }
+                    last_was_write = Some(false);
+                }
+            }
+        }
+        self.register_writes += writes;
+        Ok(())
+    }
+}
+
+/// TCA9548A with a [`SimBus`] on any of its channels
+///
+/// A transaction goes to every connected channel, and succeeds if any of them acknowledged.
+#[derive(Debug, Clone)]
+pub struct SimMux {
+    /// 7-bit address of the mux
+    pub addr: u8,
+    /// Control register, one bit per connected channel
+    pub control: u8,
+    pub channels: [Option<SimBus>; 8],
+}
+
+impl SimMux {
+    /// An empty mux at 0x70 | `a`
+    pub fn new(a: u8) -> Self {
+        Self {
+            addr: 0x70 | (a & 0b111),
+            control: 0,
+            channels: Default::default(),
+        }
+    }
+
+    /// Put a module on a channel
+    pub fn with_channel(mut self, channel: usize, bus: SimBus) -> Self {
+        self.channels[channel] = Some(bus);
+        self
+    }
+}
+
+impl ErrorType for SimMux {
+    type Error = SimError;
+}
+
+impl I2c for SimMux {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if address == self.addr {
+            for op in operations {
+                match op {
+                    Operation::Write(bytes) => {
+                        if let Some(last) = bytes.last() {
+                            self.control = *last;
+                        }
+                    }
+                    Operation::Read(buf) => buf.fill(self.control),
+                }
+            }
+            return Ok(());
+        }
+        let mut result = Err(NACK);
+        for (channel, bus) in self.channels.iter_mut().enumerate() {
+            let Some(bus) = bus else { continue };
+            if self.control & (1 << channel) == 0 {
+                continue;
+            }
+            match bus.transaction(address, operations) {
+                Err(e) if e == NACK => (),
+                other => result = other,
+            }
+        }
+        result
+    }
+}
+
+/// A delay that returns straight away, keeping count of the time asked for
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SimDelay {
+    /// Total delay requested (in ns)
+    pub elapsed_ns: u64,
+}
+
+impl DelayNs for SimDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.elapsed_ns += ns as u64;
+    }
+}
@@ -1,5 +1,6 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+pub mod bus;
 pub mod modules;
 pub mod peripherals;
 #[cfg(feature = "python")]
@@ -0,0 +1,156 @@
+//! Automatic gain control of the FRX output level with its attenuator
+
+use super::frx::{Error, Frx, FrxResult};
+use crate::peripherals::atten::{Attenuation, STEP_DB};
+use core::time::Duration;
+use embedded_hal::i2c::I2c;
+
+/// How many adjustments the controller remembers
+pub const LOG_LEN: usize = 32;
+
+/// AGC loop settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AgcConfig {
+    /// Output level to hold at the FRX power detector (in dBm)
+    pub target_dbm: f32,
+    /// Half-width of the band around the target (in dB) inside which nothing is changed
+    pub deadband_db: f32,
+    /// Fastest the attenuation may change (in dB/s), however often `update` is called
+    ///
+    /// A rate that isn't positive and finite allows a single step per update.
+    pub max_rate_db_per_s: f32,
+}
+
+impl Default for AgcConfig {
+    fn default() -> Self {
+        Self {
+            target_dbm: -20.0,
+            deadband_db: 0.5,
+            max_rate_db_per_s: 1.0,
+        }
+    }
+}
+
+/// A change the loop made to the attenuator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adjustment {
+    /// The RF power that triggered the change (in dBm)
+    pub measured_dbm: f32,
+    /// Attenuation before the change (in dB)
+    pub from_db: f32,
+    /// Attenuation after the change (in dB)
+    pub to_db: f32,
+}
+
+/// An AGC loop holding the FRX RF output level on a target
+///
+/// The controller doesn't own the module, call [`Agc::update`] periodically with it.
+#[derive(Debug, Clone)]
+pub struct Agc {
+    pub config: AgcConfig,
+    frozen: bool,
+    log: [Option<Adjustment>; LOG_LEN],
+    // Index the next adjustment will be written to
+    head: usize,
+    // Attenuation change (in dB) the rate limit allows but that hasn't been made yet
+    allowance_db: f32,
+}
+
+impl Agc {
+    pub fn new(config: AgcConfig) -> Self {
+        Self {
+            config,
+            frozen: false,
+            log: [None; LOG_LEN],
+            head: 0,
+            allowance_db: 0.0,
+        }
+    }
+
+    /// Hold the attenuator where it is (`true`) or let the loop run again (`false`)
+    pub fn freeze(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Run one iteration of the loop, `elapsed` after the previous one
+    ///
+    /// Returns the adjustment made, if any. Nothing is changed while frozen, while the level
+    /// is inside the deadband or when the attenuator is already at the end of its range. When
+    /// updates come faster than the rate limit allows a step, the allowance carries over until
+    /// a whole step is due.
+    pub fn update<I2C, E>(
+        &mut self,
+        frx: &mut Frx<I2C>,
+        elapsed: Duration,
+    ) -> FrxResult<Option<Adjustment>, E>
+    where
+        I2C: I2c<Error = E>,
+        E: embedded_hal::i2c::Error,
+    {
+        if self.frozen {
+            self.allowance_db = 0.0;
+            return Ok(None);
+        }
+        let measured_dbm = frx.adc.rf_power().map_err(|e| Error::Adc(e))?;
+        // Too much power means we need more attenuation
+        let error = measured_dbm - self.config.target_dbm;
+        let wanted = libm::roundf(error / STEP_DB) as i16;
+        if error.abs() <= self.config.deadband_db || wanted == 0 {
+            self.allowance_db = 0.0;
+            return Ok(None);
+        }
+        let rate = self.config.max_rate_db_per_s;
+        let max_steps = if rate.is_finite() && rate > 0.0 {
+            self.allowance_db += rate * elapsed.as_secs_f32();
+            (self.allowance_db / STEP_DB) as i16
+        } else {
+            1
+        };
+        let steps = wanted.clamp(-max_steps, max_steps);
+        if steps == 0 {
+            return Ok(None);
+        }
+        let from = frx.atten.get()?;
+        let word = (from as i16 + steps).clamp(0, (Attenuation::MAX_DB / STEP_DB) as i16);
+        if word == from as i16 {
+            self.allowance_db = 0.0;
+            return Ok(None);
+        }
+        // Only what the rate limit held back carries over, not what the error didn't need
+        self.allowance_db = if steps.abs() < max_steps {
+            0.0
+        } else {
+            self.allowance_db - steps.abs() as f32 * STEP_DB
+        };
+        let to_db = word as f32 * STEP_DB;
+        frx.atten.set_db(to_db)?;
+        let adjustment = Adjustment {
+            measured_dbm,
+            from_db: from.to_db(),
+            to_db,
+        };
+        self.record(adjustment);
+        Ok(Some(adjustment))
+    }
+
+    /// The logged adjustments, oldest first
+    pub fn log(&self) -> impl Iterator<Item = &Adjustment> {
+        let (newer, older) = self.log.split_at(self.head);
+        older.iter().chain(newer).flatten()
+    }
+
+    /// Forget every logged adjustment
+    pub fn clear_log(&mut self) {
+        self.log = [None; LOG_LEN];
+        self.head = 0;
+    }
+
+    fn record(&mut self, adjustment: Adjustment) {
+        self.log[self.head] = Some(adjustment);
+        self.head = (self.head + 1) % LOG_LEN;
+    }
+}
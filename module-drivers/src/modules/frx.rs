@@ -1,8 +1,13 @@
 //! The top-level FRX module driver
 
+use super::{optical::OpticalCalibration, probe::Chip, timed, RfofModule};
 use crate::peripherals::temp::TemperataureSensor;
 use crate::peripherals::{adc::frx::Adc, atten::Attenuator};
+use core::{cell::RefCell, fmt, time::Duration};
 use embedded_hal::i2c::I2c;
+#[cfg(feature = "std")]
+use embedded_hal_bus::i2c::MutexDevice;
+use embedded_hal_bus::i2c::{CriticalSectionDevice, RefCellDevice};
 
 // The fiber receiver module
 pub struct Frx<I2C> {
@@ -12,6 +17,8 @@ pub struct Frx<I2C> {
     pub adc: Adc<I2C>,
     /// Temperature sensor / ID
     pub temp: TemperataureSensor<I2C>,
+    /// Photodiode calibration, for `optical_power`
+    pub optical: Option<OpticalCalibration>,
 }
 
 #[derive(Debug)]
@@ -20,9 +27,15 @@ pub enum Error<E> {
     /// Lower-level attenuator error
     Atten(crate::peripherals::atten::Error<E>),
     /// Lower-level adc error
-    Adc(E),
+    Adc(crate::peripherals::adc::Error<E>),
     /// Lower-level temperature sensor error
     Temp(crate::peripherals::temp::Error<E>),
+    /// Attaching found a chip that hasn't been initialized
+    NotConfigured(Chip),
+    /// Optical power was requested without a photodiode calibration
+    Uncalibrated,
+    /// A calibration belongs to another module (contains the UID it was measured on)
+    WrongModule(u64),
 }
 
 // convert::from impls to use `?` in drivers to convert to top-level error
@@ -45,9 +58,72 @@ where
     }
 }
 
+impl<E> fmt::Display for Error<E>
+where
+    E: embedded_hal::i2c::Error,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Atten(e) => e.fmt(f),
+            Error::Adc(e) => e.fmt(f),
+            Error::Temp(e) => e.fmt(f),
+            Error::NotConfigured(chip) => write!(f, "{chip} hasn't been initialized"),
+            Error::Uncalibrated => write!(f, "no photodiode calibration set"),
+            Error::WrongModule(uid) => {
+                write!(f, "calibration is for the module with UID {uid:#x}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for Error<E>
+where
+    E: embedded_hal::i2c::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Atten(e) => Some(e),
+            Error::Adc(e) => Some(e),
+            Error::Temp(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 /// Result type for FRX commands
 pub type FrxResult<T, E> = Result<T, Error<E>>;
 
+/// Snapshot of every FRX monitor point
+///
+/// Each channel carries its own result, so one failed read doesn't discard the rest.
+#[derive(Debug)]
+pub struct FrxTelemetry<E> {
+    /// Board temperature (in C)
+    pub temp_c: FrxResult<f32, E>,
+    /// Unique ID
+    pub uid: FrxResult<u64, E>,
+    /// Attenuator state (in dB)
+    pub atten_db: FrxResult<f32, E>,
+    /// RF power at the power detector (in dBm)
+    pub rf_power_dbm: FrxResult<f32, E>,
+    /// DC photodiode current (in mA)
+    pub pd_current_ma: FrxResult<f32, E>,
+    /// How long the whole snapshot took to acquire (only measured with `std`)
+    pub acquisition: Option<Duration>,
+}
+
+impl<E> FrxTelemetry<E> {
+    /// Whether every channel was read successfully
+    pub fn is_complete(&self) -> bool {
+        self.temp_c.is_ok()
+            && self.uid.is_ok()
+            && self.atten_db.is_ok()
+            && self.rf_power_dbm.is_ok()
+            && self.pd_current_ma.is_ok()
+    }
+}
+
 impl<I2C, E> Frx<I2C>
 where
     I2C: I2c<Error = E>,
@@ -56,13 +132,37 @@ where
     /// Construct a new FRX instance.
     ///
     /// This requires ownership of separate I2C instances, but in reality these
-    /// will share a bus using something like refcell or mutex (embedded_hal_bus)
+    /// will share a bus using something like refcell or mutex (embedded_hal_bus),
+    /// which the single-bus constructors (`new_refcell`, `new_mutex`, ...) do for you
     pub fn new(atten_bus: I2C, adc_bus: I2C, temp_bus: I2C) -> Self {
         // Attenuator address select is tied to ground
         let temp = TemperataureSensor::new(temp_bus, 0x48);
         let atten = Attenuator::new(atten_bus, false);
         let adc = Adc::<I2C>::new(adc_bus);
-        Self { atten, adc, temp }
+        Self {
+            atten,
+            adc,
+            temp,
+            optical: None,
+        }
+    }
+
+    /// Attach to an FRX that is already running, without resetting or reconfiguring it.
+    ///
+    /// This only reads from the chips, checking they were set up by a previous `init`, and
+    /// never writes a control register. The attenuator is left as it is.
+    pub fn attach(atten_bus: I2C, adc_bus: I2C, temp_bus: I2C) -> FrxResult<Self, E> {
+        let mut frx = Self::new(atten_bus, adc_bus, temp_bus);
+        if !frx.atten.is_configured()? {
+            return Err(Error::NotConfigured(Chip::Tca6408a));
+        }
+        if !frx.adc.is_initialized().map_err(|e| Error::Adc(e))? {
+            return Err(Error::NotConfigured(Chip::Tla2528));
+        }
+        if !frx.temp.is_running()? {
+            return Err(Error::NotConfigured(Chip::Tmp117));
+        }
+        Ok(frx)
     }
 
     /// Initialize all the child peripherals
@@ -72,4 +172,129 @@ where
         self.temp.init()?;
         Ok(())
     }
+
+    /// Read every monitor point in one go
+    pub fn telemetry(&mut self) -> FrxTelemetry<E> {
+        let (mut telem, acquisition) = timed(|| FrxTelemetry {
+            temp_c: self.temp.temp().map_err(Error::Temp),
+            uid: self.temp.uid().map_err(Error::Temp),
+            atten_db: self.atten.get_db().map_err(Error::Atten),
+            rf_power_dbm: self.adc.rf_power().map_err(|e| Error::Adc(e)),
+            pd_current_ma: self.adc.pd_current().map_err(|e| Error::Adc(e)),
+            acquisition: None,
+        });
+        telem.acquisition = acquisition;
+        telem
+    }
+}
+
+/// Single-bus constructors
+///
+/// Every chip on the module sits on the same bus, these do the sharing internally.
+impl<I2C, E> Frx<I2C>
+where
+    I2C: I2c<Error = E> + Clone,
+    E: embedded_hal::i2c::Error,
+{
+    /// Construct from a single cloneable bus handle (e.g. a reference-counted shared bus)
+    pub fn new_shared(bus: I2C) -> Self {
+        Self::new(bus.clone(), bus.clone(), bus)
+    }
+
+    /// Like `attach`, from a single cloneable bus handle
+    pub fn attach_shared(bus: I2C) -> FrxResult<Self, E> {
+        Self::attach(bus.clone(), bus.clone(), bus)
+    }
+}
+
+impl<'a, I2C, E> Frx<RefCellDevice<'a, I2C>>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Construct on a bus shared through a `RefCell`, for single-threaded use
+    pub fn new_refcell(bus: &'a RefCell<I2C>) -> Self {
+        Self::new(
+            RefCellDevice::new(bus),
+            RefCellDevice::new(bus),
+            RefCellDevice::new(bus),
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, I2C, E> Frx<MutexDevice<'a, I2C>>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Construct on a bus shared through a `std` `Mutex`, for use across threads
+    pub fn new_mutex(bus: &'a std::sync::Mutex<I2C>) -> Self {
+        Self::new(
+            MutexDevice::new(bus),
+            MutexDevice::new(bus),
+            MutexDevice::new(bus),
+        )
+    }
+}
+
+impl<'a, I2C, E> Frx<CriticalSectionDevice<'a, I2C>>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Construct on a bus shared through a `critical-section` `Mutex`, for use across
+    /// interrupt priority levels
+    pub fn new_critical_section(bus: &'a critical_section::Mutex<RefCell<I2C>>) -> Self {
+        Self::new(
+            CriticalSectionDevice::new(bus),
+            CriticalSectionDevice::new(bus),
+            CriticalSectionDevice::new(bus),
+        )
+    }
+}
+
+impl<I2C, E> RfofModule for Frx<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    type Error = Error<E>;
+    type Telemetry = FrxTelemetry<E>;
+
+    fn init(&mut self) -> FrxResult<(), E> {
+        Frx::init(self)
+    }
+
+    fn temp(&mut self) -> FrxResult<f32, E> {
+        Ok(self.temp.temp()?)
+    }
+
+    fn uid(&mut self) -> FrxResult<u64, E> {
+        Ok(self.temp.uid()?)
+    }
+
+    fn atten(&mut self) -> FrxResult<f32, E> {
+        Ok(self.atten.get_db()?)
+    }
+
+    fn set_atten(&mut self, atten: f32) -> FrxResult<(), E> {
+        Ok(self.atten.set_db(atten)?)
+    }
+
+    fn rf_power(&mut self) -> FrxResult<f32, E> {
+        self.adc.rf_power().map_err(|e| Error::Adc(e))
+    }
+
+    fn pd_current(&mut self) -> FrxResult<f32, E> {
+        self.adc.pd_current().map_err(|e| Error::Adc(e))
+    }
+
+    fn optical_power(&mut self) -> FrxResult<f32, E> {
+        Frx::optical_power(self)
+    }
+
+    fn telemetry(&mut self) -> FrxTelemetry<E> {
+        Frx::telemetry(self)
+    }
 }
@@ -1,9 +1,23 @@
 //! The top-level FTX module driver
 
+use super::{
+    optical::OpticalCalibration,
+    power::PowerStep,
+    probe::Chip,
+    safety::{Action, SafetyPolicy, SafetyStatus, Trip},
+    timed, RfofModule,
+};
 use crate::peripherals::{
-    adc::ftx::Adc, atten::Attenuator, digipot::Digipot, temp::TemperataureSensor,
+    adc::ftx::Adc,
+    atten::Attenuator,
+    digipot::{Calibration, Digipot},
+    temp::TemperataureSensor,
 };
-use embedded_hal::i2c::I2c;
+use core::{cell::RefCell, fmt, time::Duration};
+use embedded_hal::{delay::DelayNs, i2c::I2c};
+#[cfg(feature = "std")]
+use embedded_hal_bus::i2c::MutexDevice;
+use embedded_hal_bus::i2c::{CriticalSectionDevice, RefCellDevice};
 
 // The fiber receiver module
 pub struct Ftx<I2C> {
@@ -13,8 +27,12 @@ pub struct Ftx<I2C> {
     pub adc: Adc<I2C>,
     /// Temperature sensor / ID
     pub temp: TemperataureSensor<I2C>,
-    /// Digipot laser current control
-    pub digipot: Digipot<I2C>,
+    /// Digipot laser current control, only ever written through the safety policy
+    digipot: Digipot<I2C>,
+    /// Laser safety limits, enforced on every laser setpoint and by `poll_safety`
+    pub safety: SafetyPolicy,
+    /// Monitor photodiode calibration, for `optical_power`
+    pub optical: Option<OpticalCalibration>,
 }
 
 #[derive(Debug)]
@@ -23,11 +41,25 @@ pub enum Error<E> {
     /// Lower-level attenuator error
     Atten(crate::peripherals::atten::Error<E>),
     /// Lower-level adc error
-    Adc(E),
+    Adc(crate::peripherals::adc::Error<E>),
     /// Lower-level temperature sensor error
     Temp(crate::peripherals::temp::Error<E>),
     /// Lower-level digipot error
     Digipot(crate::peripherals::digipot::Error<E>),
+    /// Refused a laser setpoint that would violate the safety policy
+    Unsafe(Trip),
+    /// A power sequence step failed its verification
+    Sequence(PowerStep),
+    /// The power sequence config can't be run, see `PowerConfig::is_valid`
+    InvalidConfig,
+    /// Attaching found a chip that hasn't been initialized
+    NotConfigured(Chip),
+    /// Optical power was requested without a photodiode calibration
+    Uncalibrated,
+    /// A calibration belongs to another module (contains the UID it was measured on)
+    WrongModule(u64),
+    /// A slope sweep never saw the laser above threshold, so there was nothing to fit
+    NoLasing,
 }
 
 // convert::from impls to use `?` in drivers to convert to top-level error
@@ -59,9 +91,115 @@ where
     }
 }
 
+impl<E> fmt::Display for Error<E>
+where
+    E: embedded_hal::i2c::Error,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Atten(e) => e.fmt(f),
+            Error::Adc(e) => e.fmt(f),
+            Error::Temp(e) => e.fmt(f),
+            Error::Digipot(e) => e.fmt(f),
+            Error::Unsafe(trip) => write!(f, "refused unsafe laser setpoint: {trip}"),
+            Error::Sequence(step) => write!(f, "power sequence failed at the {step} step"),
+            Error::InvalidConfig => write!(f, "invalid power sequence config"),
+            Error::NotConfigured(chip) => write!(f, "{chip} hasn't been initialized"),
+            Error::Uncalibrated => write!(f, "no photodiode calibration set"),
+            Error::WrongModule(uid) => {
+                write!(f, "calibration is for the module with UID {uid:#x}")
+            }
+            Error::NoLasing => write!(f, "laser never crossed threshold during the sweep"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for Error<E>
+where
+    E: embedded_hal::i2c::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Atten(e) => Some(e),
+            Error::Adc(e) => Some(e),
+            Error::Temp(e) => Some(e),
+            Error::Digipot(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 /// Result type for FTX commands
 pub type FtxResult<T, E> = Result<T, Error<E>>;
 
+/// Snapshot of every FTX monitor point
+///
+/// Each channel carries its own result, so one failed read doesn't discard the rest.
+#[derive(Debug)]
+pub struct FtxTelemetry<E> {
+    /// Board temperature (in C)
+    pub temp_c: FtxResult<f32, E>,
+    /// Unique ID
+    pub uid: FtxResult<u64, E>,
+    /// Attenuator state (in dB)
+    pub atten_db: FtxResult<f32, E>,
+    /// RF power at the power detector (in dBm)
+    pub rf_power_dbm: FtxResult<f32, E>,
+    /// DC monitor photodiode current (in uA)
+    pub pd_current_ua: FtxResult<f32, E>,
+    /// Laser current (in mA)
+    pub ld_current_ma: FtxResult<f32, E>,
+    /// LNA current (in mA)
+    pub lna_current_ma: FtxResult<f32, E>,
+    /// LNA voltage (in V)
+    pub lna_voltage_v: FtxResult<f32, E>,
+    /// Digital supply voltage (in V)
+    pub vdd_v: FtxResult<f32, E>,
+    /// Analog supply voltage (in V)
+    pub vdda_v: FtxResult<f32, E>,
+    /// How long the whole snapshot took to acquire (only measured with `std`)
+    pub acquisition: Option<Duration>,
+}
+
+impl<E> FtxTelemetry<E> {
+    /// Whether every channel was read successfully
+    pub fn is_complete(&self) -> bool {
+        self.temp_c.is_ok()
+            && self.uid.is_ok()
+            && self.atten_db.is_ok()
+            && self.rf_power_dbm.is_ok()
+            && self.pd_current_ua.is_ok()
+            && self.ld_current_ma.is_ok()
+            && self.lna_current_ma.is_ok()
+            && self.lna_voltage_v.is_ok()
+            && self.vdd_v.is_ok()
+            && self.vdda_v.is_ok()
+    }
+}
+
+/// Nominal digipot words per mA of laser current (the open-loop mapping)
+const WORDS_PER_MA: f32 = 255.0 / 50.0;
+
+/// Time to let the laser current and the LDI monitor settle after a wiper change
+pub(super) const LD_SETTLE_MS: u32 = 10;
+
+/// Upper bound on the number of read-back iterations while regulating
+const MAX_LD_ITERATIONS: usize = 32;
+
+/// Outcome of a closed-loop laser current regulation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LdRegulation {
+    /// Final digipot wiper word
+    pub word: u8,
+    /// Measured laser current at the final word (in mA)
+    pub current: f32,
+    /// Number of read-back iterations it took
+    pub iterations: usize,
+    /// Whether the measured current ended up within tolerance of the target
+    pub converged: bool,
+}
+
 impl<I2C, E> Ftx<I2C>
 where
     I2C: I2c<Error = E>,
@@ -70,7 +208,8 @@ where
     /// Construct a new FTX instance.
     ///
     /// This requires ownership of separate I2C instances, but in reality these
-    /// will share a bus using something like refcell or mutex (embedded_hal_bus)
+    /// will share a bus using something like refcell or mutex (embedded_hal_bus),
+    /// which the single-bus constructors (`new_refcell`, `new_mutex`, ...) do for you
     pub fn new(atten_bus: I2C, adc_bus: I2C, temp_bus: I2C, digipot_bus: I2C) -> Self {
         // Attenuator address select is tied to ground
         let temp = TemperataureSensor::new(temp_bus, 0x48);
@@ -82,7 +221,34 @@ where
             temp,
             adc,
             digipot,
+            safety: SafetyPolicy::default(),
+            optical: None,
+        }
+    }
+
+    /// Attach to an FTX that is already running, without resetting or reconfiguring it.
+    ///
+    /// This only reads from the chips, checking they were set up by a previous `init`, and
+    /// never writes a control register. The attenuator, laser and LNA are left as they are.
+    pub fn attach(
+        atten_bus: I2C,
+        adc_bus: I2C,
+        temp_bus: I2C,
+        digipot_bus: I2C,
+    ) -> FtxResult<Self, E> {
+        let mut ftx = Self::new(atten_bus, adc_bus, temp_bus, digipot_bus);
+        if !ftx.atten.is_configured()? {
+            return Err(Error::NotConfigured(Chip::Tca6408a));
+        }
+        if !ftx.adc.is_initialized().map_err(|e| Error::Adc(e))? {
+            return Err(Error::NotConfigured(Chip::Tla2528));
         }
+        if !ftx.temp.is_running()? {
+            return Err(Error::NotConfigured(Chip::Tmp117));
+        }
+        // Nothing to configure on the digipot, but make sure it's there
+        ftx.digipot.get_raw()?;
+        Ok(ftx)
     }
 
     /// Initialize all the child peripherals
@@ -93,4 +259,321 @@ where
         // Nothing to init for the digipot
         Ok(())
     }
+
+    /// The laser digipot, read-only so every setpoint goes through the safety policy
+    pub fn digipot(&self) -> &Digipot<I2C> {
+        &self.digipot
+    }
+
+    /// Start using a measured digipot transfer curve for the laser setpoints
+    pub fn set_digipot_calibration(&mut self, cal: Calibration) {
+        self.digipot.set_calibration(cal);
+    }
+
+    /// Go back to the nominal digipot mapping for the laser setpoints
+    pub fn clear_digipot_calibration(&mut self) {
+        self.digipot.clear_calibration();
+    }
+
+    /// The present laser setpoint (in mA), according to the digipot calibration
+    pub fn ld_setpoint(&mut self) -> FtxResult<f32, E> {
+        Ok(self.digipot.get()?)
+    }
+
+    /// The present raw laser digipot word
+    pub fn ld_word(&mut self) -> FtxResult<u8, E> {
+        Ok(self.digipot.get_raw()?)
+    }
+
+    /// Turn the laser off (digipot word 0), which the safety policy always allows
+    pub fn ld_off(&mut self) -> FtxResult<(), E> {
+        self.digipot.set_raw(0)?;
+        Ok(())
+    }
+
+    /// Set the laser current in mA, refusing setpoints that violate the safety policy
+    pub fn set_ld_current(&mut self, current: f32) -> FtxResult<(), E> {
+        self.check_ld_setpoint(current)?;
+        self.digipot.set(current)?;
+        Ok(())
+    }
+
+    /// Set the raw laser digipot word, checked against the safety policy like `set_ld_current`
+    pub fn set_ld_raw(&mut self, word: u8) -> FtxResult<(), E> {
+        self.check_ld_setpoint(self.digipot.current(word))?;
+        self.digipot.set_raw(word)?;
+        Ok(())
+    }
+
+    /// Check a new laser setpoint (in mA) against the safety policy, given the present conditions
+    fn check_ld_setpoint(&mut self, current: f32) -> FtxResult<(), E> {
+        let present = self.digipot.get()?;
+        let temp = self.temp.temp()?;
+        let vdd = self.adc.digital_voltage().map_err(|e| Error::Adc(e))?;
+        self.safety
+            .check_setpoint(current, present, temp, vdd)
+            .map_err(Error::Unsafe)
+    }
+
+    /// Check the laser against the safety policy
+    ///
+    /// The laser is cut back or shut down if a limit tripped.
+    pub fn poll_safety(&mut self) -> FtxResult<SafetyStatus, E> {
+        let ld_current = self.adc.ld_current().map_err(|e| Error::Adc(e))?;
+        let temp = self.temp.temp()?;
+        let vdd = self.adc.digital_voltage().map_err(|e| Error::Adc(e))?;
+        let (trip, action) = self.safety.evaluate(ld_current, temp, vdd);
+        match action {
+            Action::None => (),
+            Action::CutBack => {
+                // Back off from the setpoint, and by however far the laser overshoots it too,
+                // so the new setpoint is always a reduction the safety policy accepts
+                let setpoint = self.digipot.get()?;
+                let overshoot = (ld_current - setpoint).max(0.0);
+                let reduced = (setpoint - self.safety.cutback - overshoot)
+                    .min(self.safety.max_ld_current)
+                    .max(0.0);
+                self.set_ld_current(reduced)?;
+            }
+            Action::Shutdown => self.ld_off()?,
+        }
+        Ok(SafetyStatus {
+            ld_current,
+            temp,
+            vdd,
+            trip,
+            action,
+        })
+    }
+
+    /// Read every monitor point in one go
+    pub fn telemetry(&mut self) -> FtxTelemetry<E> {
+        let (mut telem, acquisition) = timed(|| FtxTelemetry {
+            temp_c: self.temp.temp().map_err(Error::Temp),
+            uid: self.temp.uid().map_err(Error::Temp),
+            atten_db: self.atten.get_db().map_err(Error::Atten),
+            rf_power_dbm: self.adc.rf_power().map_err(|e| Error::Adc(e)),
+            pd_current_ua: self.adc.pd_current().map_err(|e| Error::Adc(e)),
+            ld_current_ma: self.adc.ld_current().map_err(|e| Error::Adc(e)),
+            lna_current_ma: self.adc.lna_current().map_err(|e| Error::Adc(e)),
+            lna_voltage_v: self.adc.lna_voltage().map_err(|e| Error::Adc(e)),
+            vdd_v: self.adc.digital_voltage().map_err(|e| Error::Adc(e)),
+            vdda_v: self.adc.analog_voltage().map_err(|e| Error::Adc(e)),
+            acquisition: None,
+        });
+        telem.acquisition = acquisition;
+        telem
+    }
+
+    /// Drive the laser to `target` mA, using the LDI monitor as feedback.
+    ///
+    /// The target, and every step on the way to it, is checked against the safety policy. Starts
+    /// at the open-loop digipot setting and then steps the wiper word until the measured
+    /// `ld_current` is within `tolerance` mA of the target. Gives up (with `converged` unset) if
+    /// the wiper saturates or after a bounded number of iterations.
+    pub fn regulate_ld_current<D: DelayNs>(
+        &mut self,
+        target: f32,
+        tolerance: f32,
+        delay: &mut D,
+    ) -> FtxResult<LdRegulation, E> {
+        self.set_ld_current(target)?;
+        let mut word = self.digipot.get_raw()?;
+        let mut iterations = 0;
+        loop {
+            delay.delay_ms(LD_SETTLE_MS);
+            let current = self.adc.ld_current().map_err(|e| Error::Adc(e))?;
+            iterations += 1;
+
+            let err = target - current;
+            let converged = (-tolerance..=tolerance).contains(&err);
+            if converged || iterations >= MAX_LD_ITERATIONS {
+                return Ok(LdRegulation {
+                    word,
+                    current,
+                    iterations,
+                    converged,
+                });
+            }
+
+            // Half of the nominal gain so we don't ring if the real slope is steeper,
+            // but always move by at least one LSB
+            let mut step = (err * WORDS_PER_MA * 0.5) as i16;
+            if step == 0 {
+                step = if err > 0.0 { 1 } else { -1 };
+            }
+            let next = (word as i16 + step).clamp(0, 255) as u8;
+            if next == word {
+                // Pinned against the end of the wiper travel
+                return Ok(LdRegulation {
+                    word,
+                    current,
+                    iterations,
+                    converged,
+                });
+            }
+            self.set_ld_raw(next)?;
+            word = next;
+        }
+    }
+
+    /// Measure the digipot transfer curve and start using it.
+    ///
+    /// Sweeps the wiper words while reading back `ld_current`, then restores the original wiper
+    /// word (through the safety policy, so it stays lower if that is no longer safe). Every step
+    /// is checked against the safety policy at the current expected from the steps so far, and
+    /// the sweep ends before the step expected to pass the policy's `max_ld_current`. The curve
+    /// stops there, the words above it are never driven.
+    pub fn calibrate_digipot<D: DelayNs>(&mut self, delay: &mut D) -> FtxResult<Calibration, E> {
+        let original = self.digipot.get_raw()?;
+        let cal = self.measure_digipot_curve(delay);
+        let restore = self.set_ld_raw(original);
+        let cal = cal?;
+        restore?;
+        self.digipot.set_calibration(cal);
+        Ok(cal)
+    }
+
+    fn measure_digipot_curve<D: DelayNs>(&mut self, delay: &mut D) -> FtxResult<Calibration, E> {
+        let max = self.safety.max_ld_current;
+        let mut curve = [0.0; 256];
+        let mut len = 0;
+        for word in 0..=255u8 {
+            // Carry on along the last step (at least the nominal one) to predict this one
+            let expected = match len {
+                0 => 0.0,
+                1 => curve[0] + 1.0 / WORDS_PER_MA,
+                _ => curve[len - 1] + (curve[len - 1] - curve[len - 2]).max(1.0 / WORDS_PER_MA),
+            };
+            if expected > max {
+                break;
+            }
+            self.check_ld_setpoint(expected)?;
+            self.digipot.set_raw(word)?;
+            delay.delay_ms(LD_SETTLE_MS);
+            let measured = self.adc.ld_current().map_err(|e| Error::Adc(e))?;
+            if measured > max {
+                break;
+            }
+            curve[len] = measured;
+            len += 1;
+        }
+        // Nothing measured means even the bottom of the wiper is over the limit
+        Calibration::new(&curve[..len]).ok_or(Error::Unsafe(Trip::LdCurrent))
+    }
+}
+
+/// Single-bus constructors
+///
+/// Every chip on the module sits on the same bus, these do the sharing internally.
+impl<I2C, E> Ftx<I2C>
+where
+    I2C: I2c<Error = E> + Clone,
+    E: embedded_hal::i2c::Error,
+{
+    /// Construct from a single cloneable bus handle (e.g. a reference-counted shared bus)
+    pub fn new_shared(bus: I2C) -> Self {
+        Self::new(bus.clone(), bus.clone(), bus.clone(), bus)
+    }
+
+    /// Like `attach`, from a single cloneable bus handle
+    pub fn attach_shared(bus: I2C) -> FtxResult<Self, E> {
+        Self::attach(bus.clone(), bus.clone(), bus.clone(), bus)
+    }
+}
+
+impl<'a, I2C, E> Ftx<RefCellDevice<'a, I2C>>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Construct on a bus shared through a `RefCell`, for single-threaded use
+    pub fn new_refcell(bus: &'a RefCell<I2C>) -> Self {
+        Self::new(
+            RefCellDevice::new(bus),
+            RefCellDevice::new(bus),
+            RefCellDevice::new(bus),
+            RefCellDevice::new(bus),
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, I2C, E> Ftx<MutexDevice<'a, I2C>>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Construct on a bus shared through a `std` `Mutex`, for use across threads
+    pub fn new_mutex(bus: &'a std::sync::Mutex<I2C>) -> Self {
+        Self::new(
+            MutexDevice::new(bus),
+            MutexDevice::new(bus),
+            MutexDevice::new(bus),
+            MutexDevice::new(bus),
+        )
+    }
+}
+
+impl<'a, I2C, E> Ftx<CriticalSectionDevice<'a, I2C>>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Construct on a bus shared through a `critical-section` `Mutex`, for use across
+    /// interrupt priority levels
+    pub fn new_critical_section(bus: &'a critical_section::Mutex<RefCell<I2C>>) -> Self {
+        Self::new(
+            CriticalSectionDevice::new(bus),
+            CriticalSectionDevice::new(bus),
+            CriticalSectionDevice::new(bus),
+            CriticalSectionDevice::new(bus),
+        )
+    }
+}
+
+impl<I2C, E> RfofModule for Ftx<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    type Error = Error<E>;
+    type Telemetry = FtxTelemetry<E>;
+
+    fn init(&mut self) -> FtxResult<(), E> {
+        Ftx::init(self)
+    }
+
+    fn temp(&mut self) -> FtxResult<f32, E> {
+        Ok(self.temp.temp()?)
+    }
+
+    fn uid(&mut self) -> FtxResult<u64, E> {
+        Ok(self.temp.uid()?)
+    }
+
+    fn atten(&mut self) -> FtxResult<f32, E> {
+        Ok(self.atten.get_db()?)
+    }
+
+    fn set_atten(&mut self, atten: f32) -> FtxResult<(), E> {
+        Ok(self.atten.set_db(atten)?)
+    }
+
+    fn rf_power(&mut self) -> FtxResult<f32, E> {
+        self.adc.rf_power().map_err(|e| Error::Adc(e))
+    }
+
+    fn pd_current(&mut self) -> FtxResult<f32, E> {
+        // The monitor photodiode reads in uA
+        Ok(self.adc.pd_current().map_err(|e| Error::Adc(e))? / 1000.0)
+    }
+
+    fn optical_power(&mut self) -> FtxResult<f32, E> {
+        Ftx::optical_power(self)
+    }
+
+    fn telemetry(&mut self) -> FtxTelemetry<E> {
+        Ftx::telemetry(self)
+    }
 }
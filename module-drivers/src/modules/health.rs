@@ -0,0 +1,210 @@
+//! Health evaluation of module telemetry against nominal operating ranges
+
+use super::{frx::FrxTelemetry, ftx::FtxTelemetry};
+
+/// Inclusive range of values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Range {
+    pub const fn new(min: f32, max: f32) -> Self {
+        Self { min, max }
+    }
+
+    pub fn contains(&self, value: f32) -> bool {
+        (self.min..=self.max).contains(&value)
+    }
+}
+
+/// Where a parameter should sit and where it stops being acceptable
+///
+/// Values inside `nominal` are OK, values outside `nominal` but inside `alarm` are a
+/// warning and anything outside `alarm` is a fault.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub nominal: Range,
+    pub alarm: Range,
+}
+
+impl Limits {
+    pub const fn new(nominal: Range, alarm: Range) -> Self {
+        Self { nominal, alarm }
+    }
+
+    /// Classify a single value
+    pub fn status(&self, value: f32) -> Status {
+        if self.nominal.contains(value) {
+            Status::Ok
+        } else if self.alarm.contains(value) {
+            Status::Warning
+        } else {
+            Status::Fault
+        }
+    }
+
+    /// Classify a telemetry reading, a channel we couldn't read is a fault
+    pub fn check<T>(&self, reading: &Result<f32, T>) -> Status {
+        match reading {
+            Ok(value) => self.status(*value),
+            Err(_) => Status::Fault,
+        }
+    }
+}
+
+/// Health of a parameter (or of the whole module), ordered by severity
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    #[default]
+    Ok,
+    Warning,
+    Fault,
+}
+
+/// Operating ranges for the FTX
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FtxHealthConfig {
+    /// Board temperature (in C)
+    pub temp: Limits,
+    /// RF power at the power detector (in dBm)
+    pub rf_power: Limits,
+    /// Monitor photodiode current (in uA)
+    pub pd_current: Limits,
+    /// Laser current (in mA)
+    pub ld_current: Limits,
+    /// LNA current (in mA)
+    pub lna_current: Limits,
+    /// LNA voltage (in V)
+    pub lna_voltage: Limits,
+    /// Digital supply voltage (in V)
+    pub vdd: Limits,
+    /// Analog supply voltage (in V)
+    pub vdda: Limits,
+    /// Whether the LNA is expected to be on, the LNA parameters are skipped if not
+    pub check_lna: bool,
+}
+
+impl Default for FtxHealthConfig {
+    /// Ranges for the current board design: 5 V supplies, LNA biased on and the laser
+    /// run in the middle of the 0-50 mA current source
+    fn default() -> Self {
+        Self {
+            temp: Limits::new(Range::new(0.0, 50.0), Range::new(-20.0, 70.0)),
+            rf_power: Limits::new(Range::new(-40.0, 0.0), Range::new(-50.0, 10.0)),
+            pd_current: Limits::new(Range::new(100.0, 450.0), Range::new(20.0, 490.0)),
+            ld_current: Limits::new(Range::new(20.0, 45.0), Range::new(5.0, 50.0)),
+            lna_current: Limits::new(Range::new(40.0, 90.0), Range::new(20.0, 98.0)),
+            lna_voltage: Limits::new(Range::new(4.75, 5.25), Range::new(4.5, 5.5)),
+            vdd: Limits::new(Range::new(4.75, 5.25), Range::new(4.5, 5.5)),
+            vdda: Limits::new(Range::new(4.75, 5.25), Range::new(4.5, 5.5)),
+            check_lna: true,
+        }
+    }
+}
+
+/// Per-parameter health of an FTX
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FtxHealth {
+    pub temp: Status,
+    pub rf_power: Status,
+    pub pd_current: Status,
+    pub ld_current: Status,
+    pub lna_current: Status,
+    pub lna_voltage: Status,
+    pub vdd: Status,
+    pub vdda: Status,
+}
+
+impl FtxHealth {
+    /// The overall verdict, the worst of every parameter
+    pub fn overall(&self) -> Status {
+        [
+            self.temp,
+            self.rf_power,
+            self.pd_current,
+            self.ld_current,
+            self.lna_current,
+            self.lna_voltage,
+            self.vdd,
+            self.vdda,
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or_default()
+    }
+}
+
+impl FtxHealthConfig {
+    /// Evaluate a telemetry reading
+    pub fn evaluate<E>(&self, telem: &FtxTelemetry<E>) -> FtxHealth {
+        let (lna_current, lna_voltage) = if self.check_lna {
+            (
+                self.lna_current.check(&telem.lna_current_ma),
+                self.lna_voltage.check(&telem.lna_voltage_v),
+            )
+        } else {
+            (Status::Ok, Status::Ok)
+        };
+        FtxHealth {
+            temp: self.temp.check(&telem.temp_c),
+            rf_power: self.rf_power.check(&telem.rf_power_dbm),
+            pd_current: self.pd_current.check(&telem.pd_current_ua),
+            ld_current: self.ld_current.check(&telem.ld_current_ma),
+            lna_current,
+            lna_voltage,
+            vdd: self.vdd.check(&telem.vdd_v),
+            vdda: self.vdda.check(&telem.vdda_v),
+        }
+    }
+}
+
+/// Operating ranges for the FRX
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrxHealthConfig {
+    /// Board temperature (in C)
+    pub temp: Limits,
+    /// RF power at the power detector (in dBm)
+    pub rf_power: Limits,
+    /// Photodiode current (in mA)
+    pub pd_current: Limits,
+}
+
+impl Default for FrxHealthConfig {
+    /// Ranges for the current board design, with the photodiode current inside the
+    /// ~10 mA full scale of its monitor
+    fn default() -> Self {
+        Self {
+            temp: Limits::new(Range::new(0.0, 50.0), Range::new(-20.0, 70.0)),
+            rf_power: Limits::new(Range::new(-40.0, 0.0), Range::new(-50.0, 10.0)),
+            pd_current: Limits::new(Range::new(0.5, 8.0), Range::new(0.1, 9.5)),
+        }
+    }
+}
+
+/// Per-parameter health of an FRX
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrxHealth {
+    pub temp: Status,
+    pub rf_power: Status,
+    pub pd_current: Status,
+}
+
+impl FrxHealth {
+    /// The overall verdict, the worst of every parameter
+    pub fn overall(&self) -> Status {
+        self.temp.max(self.rf_power).max(self.pd_current)
+    }
+}
+
+impl FrxHealthConfig {
+    /// Evaluate a telemetry reading
+    pub fn evaluate<E>(&self, telem: &FrxTelemetry<E>) -> FrxHealth {
+        FrxHealth {
+            temp: self.temp.check(&telem.temp_c),
+            rf_power: self.rf_power.check(&telem.rf_power_dbm),
+            pd_current: self.pd_current.check(&telem.pd_current_ma),
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! An end-to-end RFoF link: an FTX driving an FRX over fiber
+
+use super::{
+    frx::{self, Frx},
+    ftx::{self, Ftx},
+    RfofModule,
+};
+use crate::peripherals::atten::{Attenuation, STEP_DB};
+use core::fmt;
+use embedded_hal::i2c::I2c;
+
+/// A link made of one FTX and the FRX at the other end of its fiber
+///
+/// The two modules can sit on different buses (or even bus types).
+pub struct Link<TX, RX> {
+    /// The transmitting module
+    pub ftx: Ftx<TX>,
+    /// The receiving module
+    pub frx: Frx<RX>,
+    /// FRX photodiode current (in mA) per FTX monitor photodiode current (in uA) with
+    /// no fiber loss, the reference that optical loss is measured against
+    pub loss_reference: Option<f32>,
+}
+
+#[derive(Debug)]
+/// Link Error types
+pub enum Error<ETX, ERX> {
+    /// Error from the FTX
+    Ftx(ftx::Error<ETX>),
+    /// Error from the FRX
+    Frx(frx::Error<ERX>),
+    /// Requested total attenuation was out of range
+    OutOfRange,
+    /// Optical loss was requested without a back-to-back reference
+    NoReference,
+    /// A photodiode reads no light, so there is no ratio to take
+    NoLight,
+}
+
+impl<ETX, ERX> fmt::Display for Error<ETX, ERX>
+where
+    ETX: embedded_hal::i2c::Error,
+    ERX: embedded_hal::i2c::Error,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Ftx(e) => write!(f, "FTX: {e}"),
+            Error::Frx(e) => write!(f, "FRX: {e}"),
+            Error::OutOfRange => write!(
+                f,
+                "total attenuation out of range (0-{} dB)",
+                2.0 * Attenuation::MAX_DB
+            ),
+            Error::NoReference => write!(f, "no back-to-back optical loss reference captured"),
+            Error::NoLight => write!(f, "a photodiode reads no light"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<ETX, ERX> std::error::Error for Error<ETX, ERX>
+where
+    ETX: embedded_hal::i2c::Error + 'static,
+    ERX: embedded_hal::i2c::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Ftx(e) => Some(e),
+            Error::Frx(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Result type for link commands
+pub type LinkResult<T, ETX, ERX> = Result<T, Error<ETX, ERX>>;
+
+impl<TX, RX, ETX, ERX> Link<TX, RX>
+where
+    TX: I2c<Error = ETX>,
+    RX: I2c<Error = ERX>,
+    ETX: embedded_hal::i2c::Error,
+    ERX: embedded_hal::i2c::Error,
+{
+    /// Pair up an FTX and an FRX
+    pub fn new(ftx: Ftx<TX>, frx: Frx<RX>) -> Self {
+        Self {
+            ftx,
+            frx,
+            loss_reference: None,
+        }
+    }
+
+    /// Split the link back up into its modules
+    pub fn into_parts(self) -> (Ftx<TX>, Frx<RX>) {
+        (self.ftx, self.frx)
+    }
+
+    /// Initialize both ends
+    pub fn init(&mut self) -> LinkResult<(), ETX, ERX> {
+        self.ftx.init().map_err(Error::Ftx)?;
+        self.frx.init().map_err(Error::Frx)?;
+        Ok(())
+    }
+
+    /// End-to-end RF gain (in dB), from the FTX power detector to the FRX power detector
+    pub fn rf_gain(&mut self) -> LinkResult<f32, ETX, ERX> {
+        let tx = RfofModule::rf_power(&mut self.ftx).map_err(Error::Ftx)?;
+        let rx = RfofModule::rf_power(&mut self.frx).map_err(Error::Frx)?;
+        Ok(rx - tx)
+    }
+
+    /// The ratio of FRX photodiode current (in mA) to FTX monitor photodiode current (in uA)
+    ///
+    /// Returns [`Error::NoLight`] unless both photodiodes read a positive current.
+    pub fn pd_ratio(&mut self) -> LinkResult<f32, ETX, ERX> {
+        let tx = self
+            .ftx
+            .adc
+            .pd_current()
+            .map_err(|e| Error::Ftx(ftx::Error::Adc(e)))?;
+        let rx = self
+            .frx
+            .adc
+            .pd_current()
+            .map_err(|e| Error::Frx(frx::Error::Adc(e)))?;
+        if tx <= 0.0 || rx <= 0.0 {
+            return Err(Error::NoLight);
+        }
+        Ok(rx / tx)
+    }
+
+    /// Record the present photodiode ratio as the zero-loss reference
+    ///
+    /// Do this with the modules connected back-to-back by a short patch cord.
+    pub fn capture_loss_reference(&mut self) -> LinkResult<f32, ETX, ERX> {
+        let ratio = self.pd_ratio()?;
+        self.loss_reference = Some(ratio);
+        Ok(ratio)
+    }
+
+    /// Optical link loss (in dB) relative to the back-to-back reference
+    ///
+    /// The monitor photodiode tracks the launched power and the FRX photodiode the received
+    /// power, so the change in their ratio is the loss of the fiber in between.
+    pub fn optical_loss(&mut self) -> LinkResult<f32, ETX, ERX> {
+        let reference = self.loss_reference.ok_or(Error::NoReference)?;
+        let ratio = self.pd_ratio()?;
+        Ok(10.0 * libm::log10f(reference / ratio))
+    }
+
+    /// Total attenuation across both attenuators (in dB)
+    pub fn total_atten(&mut self) -> LinkResult<f32, ETX, ERX> {
+        let tx = self
+            .ftx
+            .atten
+            .get_db()
+            .map_err(|e| Error::Ftx(ftx::Error::Atten(e)))?;
+        let rx = self
+            .frx
+            .atten
+            .get_db()
+            .map_err(|e| Error::Frx(frx::Error::Atten(e)))?;
+        Ok(tx + rx)
+    }
+
+    /// Set the total attenuation (in dB, 0-63.5), split evenly across both attenuators
+    ///
+    /// The total is rounded to the nearest 0.25 dB step. When the number of 0.25 dB steps is
+    /// odd, the FRX gets the extra step. Returns the FTX and FRX attenuations that were set.
+    pub fn set_total_atten(&mut self, db: f32) -> LinkResult<(f32, f32), ETX, ERX> {
+        if !(0.0..=2.0 * Attenuation::MAX_DB).contains(&db) {
+            return Err(Error::OutOfRange);
+        }
+        let steps = libm::roundf(db / STEP_DB) as u8;
+        let tx_steps = steps / 2;
+        let rx_steps = steps - tx_steps;
+        let tx = tx_steps as f32 * STEP_DB;
+        let rx = rx_steps as f32 * STEP_DB;
+        self.ftx
+            .atten
+            .set_db(tx)
+            .map_err(|e| Error::Ftx(ftx::Error::Atten(e)))?;
+        self.frx
+            .atten
+            .set_db(rx)
+            .map_err(|e| Error::Frx(frx::Error::Atten(e)))?;
+        Ok((tx, rx))
+    }
+}
@@ -1,2 +1,67 @@
+use core::time::Duration;
+
+pub mod agc;
 pub mod frx;
 pub mod ftx;
+pub mod health;
+pub mod link;
+pub mod optical;
+pub mod power;
+pub mod probe;
+#[cfg(feature = "std")]
+pub mod registry;
+pub mod safety;
+pub mod slope;
+pub mod state;
+
+/// The monitor and control points common to the FTX and FRX
+///
+/// This lets monitoring and control code be written once, generic over the module type.
+pub trait RfofModule {
+    /// Top-level error type of the module
+    type Error;
+    /// Snapshot of every monitor point on the module
+    type Telemetry;
+
+    /// Initialize all the child peripherals
+    fn init(&mut self) -> Result<(), Self::Error>;
+
+    /// Get the board temperature (in C)
+    fn temp(&mut self) -> Result<f32, Self::Error>;
+
+    /// Get the unique ID
+    fn uid(&mut self) -> Result<u64, Self::Error>;
+
+    /// Get the state of the digital step attenuator (in dB)
+    fn atten(&mut self) -> Result<f32, Self::Error>;
+
+    /// Set the state of the digital step attenuator (in dB)
+    fn set_atten(&mut self, atten: f32) -> Result<(), Self::Error>;
+
+    /// Get the RF power at the power detector (in dBm)
+    fn rf_power(&mut self) -> Result<f32, Self::Error>;
+
+    /// Get the DC photodiode current (in mA)
+    fn pd_current(&mut self) -> Result<f32, Self::Error>;
+
+    /// Get the optical power (in dBm) estimated from the photodiode current, see
+    /// [`optical::OpticalCalibration`]
+    fn optical_power(&mut self) -> Result<f32, Self::Error>;
+
+    /// Read every monitor point, a failing channel doesn't discard the others
+    fn telemetry(&mut self) -> Self::Telemetry;
+}
+
+/// Run a telemetry acquisition, timing it if we have a clock
+fn timed<T>(acquire: impl FnOnce() -> T) -> (T, Option<Duration>) {
+    #[cfg(feature = "std")]
+    {
+        let start = std::time::Instant::now();
+        let res = acquire();
+        (res, Some(start.elapsed()))
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        (acquire(), None)
+    }
+}
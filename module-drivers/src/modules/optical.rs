@@ -0,0 +1,102 @@
+//! Optical power estimates from the photodiode currents
+
+use super::{
+    frx::{self, Frx, FrxResult},
+    ftx::{self, Ftx, FtxResult},
+};
+use embedded_hal::i2c::I2c;
+use serde::{Deserialize, Serialize};
+
+/// Planck constant times the speed of light over the electron charge (in W nm / A)
+const HC_OVER_Q: f32 = 1239.84;
+
+/// Photodiode calibration of one module, tied to it by its unique ID
+///
+/// On the FRX this is the receive photodiode. On the FTX it is the monitor photodiode, whose
+/// responsivity is taken relative to the laser output (monitor current per watt launched), so
+/// it folds in the tap ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OpticalCalibration {
+    /// Unique ID of the module this calibration was measured on
+    pub uid: u64,
+    /// Photodiode responsivity (in A/W)
+    pub responsivity: f32,
+    /// Wavelength the responsivity applies to (in nm)
+    pub wavelength_nm: f32,
+}
+
+impl OpticalCalibration {
+    pub fn new(uid: u64, responsivity: f32, wavelength_nm: f32) -> Self {
+        Self {
+            uid,
+            responsivity,
+            wavelength_nm,
+        }
+    }
+
+    /// Calibration from a quantum efficiency (0-1) at a wavelength (in nm)
+    pub fn from_quantum_efficiency(uid: u64, efficiency: f32, wavelength_nm: f32) -> Self {
+        Self::new(uid, efficiency * wavelength_nm / HC_OVER_Q, wavelength_nm)
+    }
+
+    /// Optical power (in mW) for a photodiode current (in mA)
+    pub fn power_mw(&self, current_ma: f32) -> f32 {
+        current_ma / self.responsivity
+    }
+
+    /// Optical power (in dBm) for a photodiode current (in mA)
+    pub fn power_dbm(&self, current_ma: f32) -> f32 {
+        mw_to_dbm(self.power_mw(current_ma))
+    }
+}
+
+/// Convert a power in mW to dBm
+pub fn mw_to_dbm(mw: f32) -> f32 {
+    10.0 * libm::log10f(mw)
+}
+
+impl<I2C, E> Ftx<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Use a monitor photodiode calibration, checking it was measured on this module
+    pub fn set_optical_calibration(&mut self, cal: OpticalCalibration) -> FtxResult<(), E> {
+        let uid = self.temp.uid()?;
+        if uid != cal.uid {
+            return Err(ftx::Error::WrongModule(cal.uid));
+        }
+        self.optical = Some(cal);
+        Ok(())
+    }
+
+    /// Estimated laser optical output (in dBm) from the monitor photodiode
+    pub fn optical_power(&mut self) -> FtxResult<f32, E> {
+        let cal = self.optical.ok_or(ftx::Error::Uncalibrated)?;
+        let current_ua = self.adc.pd_current().map_err(|e| ftx::Error::Adc(e))?;
+        Ok(cal.power_dbm(current_ua / 1000.0))
+    }
+}
+
+impl<I2C, E> Frx<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Use a receive photodiode calibration, checking it was measured on this module
+    pub fn set_optical_calibration(&mut self, cal: OpticalCalibration) -> FrxResult<(), E> {
+        let uid = self.temp.uid()?;
+        if uid != cal.uid {
+            return Err(frx::Error::WrongModule(cal.uid));
+        }
+        self.optical = Some(cal);
+        Ok(())
+    }
+
+    /// Received optical power (in dBm) from the photodiode
+    pub fn optical_power(&mut self) -> FrxResult<f32, E> {
+        let cal = self.optical.ok_or(frx::Error::Uncalibrated)?;
+        let current_ma = self.adc.pd_current().map_err(|e| frx::Error::Adc(e))?;
+        Ok(cal.power_dbm(current_ma))
+    }
+}
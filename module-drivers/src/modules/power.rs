@@ -0,0 +1,230 @@
+//! Ordered power-up and power-down sequencing for the FTX
+
+use super::{
+    ftx::{Error, Ftx, FtxResult},
+    health::{FtxHealthConfig, Range},
+};
+use core::fmt;
+use embedded_hal::{delay::DelayNs, i2c::I2c};
+
+/// The stages of the sequence, in power-up order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerStep {
+    /// VDD and VDDA
+    Supplies,
+    /// LNA bias
+    Lna,
+    /// Laser current
+    Laser,
+    /// Monitor photodiode (the laser is actually emitting)
+    Photodiode,
+}
+
+impl fmt::Display for PowerStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerStep::Supplies => write!(f, "supplies"),
+            PowerStep::Lna => write!(f, "LNA"),
+            PowerStep::Laser => write!(f, "laser"),
+            PowerStep::Photodiode => write!(f, "photodiode"),
+        }
+    }
+}
+
+/// Upper bound on the number of steps in a laser ramp, however short `ramp_step` is
+const MAX_RAMP_STEPS: u32 = 1000;
+
+/// Parameters of the power sequences
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerConfig {
+    /// Laser current to ramp to (in mA)
+    pub ld_current: f32,
+    /// Laser current increment per ramp step (in mA)
+    pub ramp_step: f32,
+    /// Time between laser ramp steps (in ms)
+    pub ramp_delay_ms: u32,
+    /// Time for the LNA bias to settle after switching it (in ms)
+    pub lna_settle_ms: u32,
+    /// Acceptable digital supply voltage (in V)
+    pub vdd: Range,
+    /// Acceptable analog supply voltage (in V)
+    pub vdda: Range,
+    /// Acceptable LNA current with the LNA on (in mA)
+    pub lna_current: Range,
+    /// Acceptable monitor photodiode current with the laser on (in uA)
+    pub pd_current: Range,
+    /// Currents (in mA) below which the laser and LNA count as off
+    pub off_current: f32,
+}
+
+impl Default for PowerConfig {
+    /// Ramp to 25 mA in 1 mA steps, verifying against the alarm ranges of the default health config
+    fn default() -> Self {
+        let health = FtxHealthConfig::default();
+        Self {
+            ld_current: 25.0,
+            ramp_step: 1.0,
+            ramp_delay_ms: 20,
+            lna_settle_ms: 50,
+            vdd: health.vdd.alarm,
+            vdda: health.vdda.alarm,
+            lna_current: health.lna_current.alarm,
+            pd_current: health.pd_current.alarm,
+            off_current: 1.0,
+        }
+    }
+}
+
+impl PowerConfig {
+    /// Whether a power-up can run with these, a positive ramp step and a laser current that
+    /// isn't negative (both finite)
+    pub fn is_valid(&self) -> bool {
+        self.ramp_step.is_finite()
+            && self.ramp_step > 0.0
+            && self.ld_current.is_finite()
+            && self.ld_current >= 0.0
+    }
+}
+
+/// The readings each step of a successful power-up was verified with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerUpReport {
+    /// Digital supply voltage (in V)
+    pub vdd: f32,
+    /// Analog supply voltage (in V)
+    pub vdda: f32,
+    /// LNA current (in mA)
+    pub lna_current: f32,
+    /// Laser current (in mA)
+    pub ld_current: f32,
+    /// Monitor photodiode current (in uA)
+    pub pd_current: f32,
+}
+
+impl<I2C, E> Ftx<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Bring the FTX up in order: verify the supplies, enable and check the LNA, ramp the laser
+    /// from its present setpoint (through the safety policy) and confirm the photodiode sees it.
+    ///
+    /// A config that isn't [`PowerConfig::is_valid`] is refused with [`Error::InvalidConfig`].
+    /// If the supplies are out of range nothing has been switched yet, so nothing is touched.
+    /// If any later step fails, everything is powered back down before returning the error.
+    pub fn power_up<D: DelayNs>(
+        &mut self,
+        config: &PowerConfig,
+        delay: &mut D,
+    ) -> FtxResult<PowerUpReport, E> {
+        if !config.is_valid() {
+            return Err(Error::InvalidConfig);
+        }
+        let vdd = self.adc.digital_voltage().map_err(|e| Error::Adc(e))?;
+        let vdda = self.adc.analog_voltage().map_err(|e| Error::Adc(e))?;
+        if !config.vdd.contains(vdd) || !config.vdda.contains(vdda) {
+            return Err(Error::Sequence(PowerStep::Supplies));
+        }
+
+        let res = self.power_up_steps(config, vdd, vdda, delay);
+        if res.is_err() {
+            // Best effort, the original failure is the interesting one
+            let _ = self.power_down(config, delay);
+        }
+        res
+    }
+
+    fn power_up_steps<D: DelayNs>(
+        &mut self,
+        config: &PowerConfig,
+        vdd: f32,
+        vdda: f32,
+        delay: &mut D,
+    ) -> FtxResult<PowerUpReport, E> {
+        self.adc.enable_lna(true).map_err(|e| Error::Adc(e))?;
+        delay.delay_ms(config.lna_settle_ms);
+        let lna_current = self.adc.lna_current().map_err(|e| Error::Adc(e))?;
+        if !config.lna_current.contains(lna_current) {
+            return Err(Error::Sequence(PowerStep::Lna));
+        }
+
+        self.ramp_ld(config.ld_current, config, delay)?;
+        let ld_current = self.adc.ld_current().map_err(|e| Error::Adc(e))?;
+        if ld_current < config.off_current {
+            return Err(Error::Sequence(PowerStep::Laser));
+        }
+
+        let pd_current = self.adc.pd_current().map_err(|e| Error::Adc(e))?;
+        if !config.pd_current.contains(pd_current) {
+            return Err(Error::Sequence(PowerStep::Photodiode));
+        }
+
+        Ok(PowerUpReport {
+            vdd,
+            vdda,
+            lna_current,
+            ld_current,
+            pd_current,
+        })
+    }
+
+    /// Bring the FTX down in order: ramp the laser off, then disable the LNA, verifying each.
+    ///
+    /// Both steps are always attempted, the first failure is returned. Any config is accepted
+    /// here, a ramp step that isn't positive turns the laser down in a single step.
+    pub fn power_down<D: DelayNs>(
+        &mut self,
+        config: &PowerConfig,
+        delay: &mut D,
+    ) -> FtxResult<(), E> {
+        let laser = self.laser_down(config, delay);
+        let lna = self.lna_down(config, delay);
+        laser.and(lna)
+    }
+
+    fn laser_down<D: DelayNs>(&mut self, config: &PowerConfig, delay: &mut D) -> FtxResult<(), E> {
+        // Turning down is always allowed by the safety policy, even with a limit tripped
+        self.ramp_ld(0.0, config, delay)?;
+        self.ld_off()?;
+        delay.delay_ms(config.ramp_delay_ms);
+        if self.adc.ld_current().map_err(|e| Error::Adc(e))? > config.off_current {
+            return Err(Error::Sequence(PowerStep::Laser));
+        }
+        Ok(())
+    }
+
+    /// Ramp the laser from its present setpoint to `target` (in mA), in even steps of at most
+    /// `ramp_step` but never more than [`MAX_RAMP_STEPS`] of them
+    fn ramp_ld<D: DelayNs>(
+        &mut self,
+        target: f32,
+        config: &PowerConfig,
+        delay: &mut D,
+    ) -> FtxResult<(), E> {
+        let mut from = self.ld_setpoint()?;
+        if !from.is_finite() {
+            // No telling where the laser is, so start over from off
+            self.ld_off()?;
+            from = 0.0;
+        }
+        // A NaN step count (from a NaN ramp step) ends up as a single step
+        let steps = libm::ceilf((target - from).abs() / config.ramp_step)
+            .max(1.0)
+            .min(MAX_RAMP_STEPS as f32) as u32;
+        for step in 1..=steps {
+            let setpoint = from + (target - from) * step as f32 / steps as f32;
+            self.set_ld_current(setpoint)?;
+            delay.delay_ms(config.ramp_delay_ms);
+        }
+        Ok(())
+    }
+
+    fn lna_down<D: DelayNs>(&mut self, config: &PowerConfig, delay: &mut D) -> FtxResult<(), E> {
+        self.adc.enable_lna(false).map_err(|e| Error::Adc(e))?;
+        delay.delay_ms(config.lna_settle_ms);
+        if self.adc.lna_current().map_err(|e| Error::Adc(e))? > config.off_current {
+            return Err(Error::Sequence(PowerStep::Lna));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+//! Hardware presence probing and module-type detection
+//!
+//! Every module sits its chips on the same fixed addresses, so we can tell what is on a
+//! bus by which of them acknowledge.
+
+use core::fmt;
+use embedded_hal::i2c::{Error as _, ErrorKind, I2c};
+use serde::{Deserialize, Serialize};
+
+pub use crate::peripherals::Chip;
+
+impl Chip {
+    /// Every chip, in probe order
+    pub const ALL: [Chip; 4] = [Chip::Tmp117, Chip::Tla2528, Chip::Tca6408a, Chip::Cat5171];
+
+    /// 7-bit I2C address of the chip, as strapped on both modules
+    pub fn addr(&self) -> u8 {
+        match self {
+            Chip::Tmp117 => 0x48,
+            Chip::Tla2528 => 0x10,
+            Chip::Tca6408a => 0x20,
+            Chip::Cat5171 => 0x2C,
+        }
+    }
+}
+
+impl fmt::Display for Chip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (0x{:02X})", self.name(), self.addr())
+    }
+}
+
+/// The kind of module on a bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModuleKind {
+    Ftx,
+    Frx,
+    Unknown,
+}
+
+impl ModuleKind {
+    /// The chips this kind of module carries
+    pub fn chips(&self) -> &'static [Chip] {
+        match self {
+            ModuleKind::Ftx => &Chip::ALL,
+            ModuleKind::Frx => &[Chip::Tmp117, Chip::Tla2528, Chip::Tca6408a],
+            ModuleKind::Unknown => &[],
+        }
+    }
+}
+
+impl fmt::Display for ModuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleKind::Ftx => write!(f, "FTX"),
+            ModuleKind::Frx => write!(f, "FRX"),
+            ModuleKind::Unknown => write!(f, "unknown board"),
+        }
+    }
+}
+
+/// Which of the expected chips acknowledged
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeReport {
+    pub tmp117: bool,
+    pub tla2528: bool,
+    pub tca6408a: bool,
+    pub cat5171: bool,
+}
+
+impl ProbeReport {
+    /// Whether a given chip acknowledged
+    pub fn present(&self, chip: Chip) -> bool {
+        match chip {
+            Chip::Tmp117 => self.tmp117,
+            Chip::Tla2528 => self.tla2528,
+            Chip::Tca6408a => self.tca6408a,
+            Chip::Cat5171 => self.cat5171,
+        }
+    }
+
+    /// Decide what is on the bus, the digipot is the only difference between the two modules
+    pub fn kind(&self) -> ModuleKind {
+        match (self.tmp117 && self.tla2528 && self.tca6408a, self.cat5171) {
+            (true, true) => ModuleKind::Ftx,
+            (true, false) => ModuleKind::Frx,
+            _ => ModuleKind::Unknown,
+        }
+    }
+
+    /// Check that the bus holds the expected kind of module, reporting the first missing chip
+    pub fn expect(&self, kind: ModuleKind) -> Result<(), Chip> {
+        match kind.chips().iter().find(|chip| !self.present(**chip)) {
+            Some(chip) => Err(*chip),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for ProbeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "detected {}:", self.kind())?;
+        for chip in Chip::ALL {
+            let state = if self.present(chip) {
+                "present"
+            } else {
+                "missing"
+            };
+            write!(f, " {chip} {state};")?;
+        }
+        Ok(())
+    }
+}
+
+/// Check which of the expected chips acknowledge on a bus
+///
+/// This does a single-byte read from each address, which has no side effects on any of the
+/// chips. A NACK means the chip is absent, any other bus error is returned as-is.
+pub fn probe<I2C: I2c>(bus: &mut I2C) -> Result<ProbeReport, I2C::Error> {
+    let mut ack = |chip: Chip| -> Result<bool, I2C::Error> {
+        let mut byte = [0u8; 1];
+        match bus.read(chip.addr(), &mut byte) {
+            Ok(()) => Ok(true),
+            Err(e) if matches!(e.kind(), ErrorKind::NoAcknowledge(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    };
+    Ok(ProbeReport {
+        tmp117: ack(Chip::Tmp117)?,
+        tla2528: ack(Chip::Tla2528)?,
+        tca6408a: ack(Chip::Tca6408a)?,
+        cat5171: ack(Chip::Cat5171)?,
+    })
+}
@@ -0,0 +1,291 @@
+//! Registry of known modules, keyed by the TMP117 unique ID
+//!
+//! The UID is the only identity a module carries, the registry ties it to a serial number, the
+//! kind of module, the antenna or signal path it belongs to and its per-unit calibration. It is
+//! a plain serde struct, read and written as TOML or JSON through [`StateFile`]:
+//!
+//! ```toml
+//! [[module]]
+//! uid = 0x1234_5678_9abc
+//! serial = "FTX-0042"
+//! kind = "Ftx"
+//! label = "ant-017/pol-x"
+//!
+//! [module.calibration.optical]
+//! responsivity = 0.1
+//! wavelength_nm = 1310.0
+//! ```
+
+use super::{
+    frx::{Frx, FrxResult},
+    ftx::{Ftx, FtxResult},
+    optical::OpticalCalibration,
+    probe::ModuleKind,
+    state::StateFile,
+};
+use crate::peripherals::digipot::Calibration;
+use embedded_hal::i2c::I2c;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Photodiode calibration of a registered unit, see [`OpticalCalibration`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Photodiode {
+    /// Photodiode responsivity (in A/W)
+    pub responsivity: f32,
+    /// Wavelength the responsivity applies to (in nm)
+    pub wavelength_nm: f32,
+}
+
+/// Per-unit calibration, applied when a module is identified
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnitCalibration {
+    /// Photodiode calibration, for optical power estimates
+    #[serde(default)]
+    pub optical: Option<Photodiode>,
+    /// Measured laser current (in mA) from digipot wiper word 0 up (FTX only), as in
+    /// [`Calibration::points`]
+    ///
+    /// A measured curve stops short of word 255 when the current limit was reached first, the
+    /// words past its end are extrapolated. Every entry must be finite, JSON has no infinity.
+    #[serde(default)]
+    pub digipot: Option<Vec<f32>>,
+}
+
+/// Everything the registry knows about one unit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModuleEntry {
+    /// TMP117 unique ID
+    pub uid: u64,
+    /// Human-readable serial number
+    pub serial: String,
+    /// Which kind of module this is
+    pub kind: ModuleKind,
+    /// Antenna or signal path the unit is installed in, if assigned
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub calibration: UnitCalibration,
+}
+
+/// Something unexpected about the unit found at a location
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// The UID isn't in the registry
+    Unknown(u64),
+    /// The unit is registered as a different kind of module
+    WrongKind {
+        serial: String,
+        registered: ModuleKind,
+        found: ModuleKind,
+    },
+    /// The unit is registered to a different location (or none)
+    WrongLocation {
+        serial: String,
+        location: String,
+        registered: Option<String>,
+        /// Serial of the unit registered to this location, if any
+        expected: Option<String>,
+    },
+    /// The digipot curve is empty, longer than 256 entries or not finite (contains its length)
+    BadDigipotCurve(usize),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::Unknown(uid) => write!(f, "module with UID {uid:#x} isn't registered"),
+            Warning::WrongKind {
+                serial,
+                registered,
+                found,
+            } => write!(
+                f,
+                "{serial} is registered as an {registered}, found an {found}"
+            ),
+            Warning::WrongLocation {
+                serial,
+                location,
+                registered,
+                expected,
+            } => {
+                write!(f, "{serial} found at {location}")?;
+                match registered {
+                    Some(registered) => write!(f, " but is registered to {registered}")?,
+                    None => write!(f, " but isn't assigned to a location")?,
+                }
+                if let Some(expected) = expected {
+                    write!(f, ", expected {expected}")?;
+                }
+                Ok(())
+            }
+            Warning::BadDigipotCurve(len) => {
+                write!(
+                    f,
+                    "digipot curve of {len} entries isn't 1 to 256 finite currents, ignoring it"
+                )
+            }
+        }
+    }
+}
+
+/// The result of looking up a module in the registry
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    /// UID read from the module
+    pub uid: u64,
+    /// Registry entry for the UID, if there is one
+    pub entry: Option<ModuleEntry>,
+    /// Everything that didn't match what was expected, empty if all is well
+    pub warnings: Vec<Warning>,
+}
+
+impl Identity {
+    /// Serial number, if the module is registered
+    pub fn serial(&self) -> Option<&str> {
+        self.entry.as_ref().map(|e| e.serial.as_str())
+    }
+
+    /// Photodiode calibration from the registry, if there is one
+    pub fn optical_calibration(&self) -> Option<OpticalCalibration> {
+        let pd = self.entry.as_ref()?.calibration.optical?;
+        Some(OpticalCalibration::new(
+            self.uid,
+            pd.responsivity,
+            pd.wavelength_nm,
+        ))
+    }
+
+    /// Digipot calibration from the registry, if there is a well-formed one
+    pub fn digipot_calibration(&self) -> Option<Calibration> {
+        let curve = self.entry.as_ref()?.calibration.digipot.as_ref()?;
+        Calibration::new(curve)
+    }
+}
+
+/// Every known module
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Registry {
+    #[serde(default, rename = "module")]
+    pub modules: Vec<ModuleEntry>,
+}
+
+impl StateFile for Registry {}
+
+impl Registry {
+    /// Look up a unit by UID
+    pub fn get(&self, uid: u64) -> Option<&ModuleEntry> {
+        self.modules.iter().find(|e| e.uid == uid)
+    }
+
+    /// Look up a unit by serial number
+    pub fn by_serial(&self, serial: &str) -> Option<&ModuleEntry> {
+        self.modules.iter().find(|e| e.serial == serial)
+    }
+
+    /// Look up the unit of a given kind registered to a location
+    pub fn at(&self, label: &str, kind: ModuleKind) -> Option<&ModuleEntry> {
+        self.modules
+            .iter()
+            .find(|e| e.kind == kind && e.label.as_deref() == Some(label))
+    }
+
+    /// Add a unit, replacing any entry with the same UID
+    pub fn insert(&mut self, entry: ModuleEntry) {
+        match self.modules.iter_mut().find(|e| e.uid == entry.uid) {
+            Some(existing) => *existing = entry,
+            None => self.modules.push(entry),
+        }
+    }
+
+    /// Identify the unit with `uid`, found to be a `kind` module, at an optional location
+    pub fn resolve(&self, uid: u64, kind: ModuleKind, location: Option<&str>) -> Identity {
+        let mut warnings = Vec::new();
+        let entry = self.get(uid).cloned();
+        match &entry {
+            None => warnings.push(Warning::Unknown(uid)),
+            Some(entry) => {
+                if entry.kind != kind {
+                    warnings.push(Warning::WrongKind {
+                        serial: entry.serial.clone(),
+                        registered: entry.kind,
+                        found: kind,
+                    });
+                }
+                if let Some(location) = location {
+                    if entry.label.as_deref() != Some(location) {
+                        warnings.push(Warning::WrongLocation {
+                            serial: entry.serial.clone(),
+                            location: location.to_string(),
+                            registered: entry.label.clone(),
+                            expected: self.at(location, kind).map(|e| e.serial.clone()),
+                        });
+                    }
+                }
+                if let Some(curve) = &entry.calibration.digipot {
+                    if Calibration::new(curve).is_none() {
+                        warnings.push(Warning::BadDigipotCurve(curve.len()));
+                    }
+                }
+            }
+        }
+        Identity {
+            uid,
+            entry,
+            warnings,
+        }
+    }
+}
+
+impl<I2C, E> Ftx<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Look this module up in a registry, and use its calibration
+    ///
+    /// The calibration is only applied if the unit is registered as an FTX. Mismatches (unknown
+    /// unit, wrong kind, wrong location) are returned as warnings, not errors.
+    pub fn identify(
+        &mut self,
+        registry: &Registry,
+        location: Option<&str>,
+    ) -> FtxResult<Identity, E> {
+        let uid = self.temp.uid()?;
+        let identity = registry.resolve(uid, ModuleKind::Ftx, location);
+        if identity.entry.as_ref().map(|e| e.kind) == Some(ModuleKind::Ftx) {
+            if let Some(cal) = identity.optical_calibration() {
+                self.optical = Some(cal);
+            }
+            if let Some(cal) = identity.digipot_calibration() {
+                self.set_digipot_calibration(cal);
+            }
+        }
+        Ok(identity)
+    }
+}
+
+impl<I2C, E> Frx<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Look this module up in a registry, and use its calibration
+    ///
+    /// The calibration is only applied if the unit is registered as an FRX. Mismatches (unknown
+    /// unit, wrong kind, wrong location) are returned as warnings, not errors.
+    pub fn identify(
+        &mut self,
+        registry: &Registry,
+        location: Option<&str>,
+    ) -> FrxResult<Identity, E> {
+        let uid = self.temp.uid()?;
+        let identity = registry.resolve(uid, ModuleKind::Frx, location);
+        if identity.entry.as_ref().map(|e| e.kind) == Some(ModuleKind::Frx) {
+            if let Some(cal) = identity.optical_calibration() {
+                self.optical = Some(cal);
+            }
+        }
+        Ok(identity)
+    }
+}
@@ -0,0 +1,119 @@
+//! Laser safety limits for the FTX
+
+use core::fmt;
+
+/// Operating limits the FTX laser is kept within
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SafetyPolicy {
+    /// Maximum laser current (in mA)
+    pub max_ld_current: f32,
+    /// Maximum board temperature (in C)
+    pub max_temp: f32,
+    /// Minimum digital supply (VDD) voltage (in V)
+    pub min_vdd: f32,
+    /// How far to back off the laser current (in mA) every time a cutback trips
+    pub cutback: f32,
+    /// Margin above `max_temp` (in C) past which we shut the laser off instead of cutting back
+    pub shutdown_margin: f32,
+}
+
+impl Default for SafetyPolicy {
+    /// The full range of the current source, with conservative thermal and supply limits
+    fn default() -> Self {
+        Self {
+            max_ld_current: 50.0,
+            max_temp: 70.0,
+            min_vdd: 4.5,
+            cutback: 5.0,
+            shutdown_margin: 10.0,
+        }
+    }
+}
+
+/// Which limit was violated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trip {
+    /// Laser current above `max_ld_current`
+    LdCurrent,
+    /// Board temperature above `max_temp`
+    Temperature,
+    /// Digital supply below `min_vdd`
+    Vdd,
+}
+
+impl fmt::Display for Trip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trip::LdCurrent => write!(f, "laser current above limit"),
+            Trip::Temperature => write!(f, "board temperature above limit"),
+            Trip::Vdd => write!(f, "digital supply below limit"),
+        }
+    }
+}
+
+/// What was done to the laser in response to a trip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Everything within limits
+    None,
+    /// Laser current was reduced by `cutback`
+    CutBack,
+    /// Laser was turned off
+    Shutdown,
+}
+
+/// The readings a safety poll was based on, and its outcome
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SafetyStatus {
+    /// Measured laser current (in mA)
+    pub ld_current: f32,
+    /// Board temperature (in C)
+    pub temp: f32,
+    /// Digital supply voltage (in V)
+    pub vdd: f32,
+    /// The limit that tripped, if any
+    pub trip: Option<Trip>,
+    /// The action taken
+    pub action: Action,
+}
+
+impl SafetyPolicy {
+    /// Whether a new laser setpoint (in mA) is acceptable given the present setpoint (in mA) and
+    /// conditions
+    pub fn check_setpoint(
+        &self,
+        current: f32,
+        present: f32,
+        temp: f32,
+        vdd: f32,
+    ) -> Result<(), Trip> {
+        // Turning the laser down or off is always allowed, even with a limit tripped
+        if current <= 0.0 || current <= present {
+            return Ok(());
+        }
+        if current > self.max_ld_current {
+            Err(Trip::LdCurrent)
+        } else if temp > self.max_temp {
+            Err(Trip::Temperature)
+        } else if vdd < self.min_vdd {
+            Err(Trip::Vdd)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Decide what to do given the present readings, most severe trip first
+    pub fn evaluate(&self, ld_current: f32, temp: f32, vdd: f32) -> (Option<Trip>, Action) {
+        if vdd < self.min_vdd {
+            (Some(Trip::Vdd), Action::Shutdown)
+        } else if temp > self.max_temp + self.shutdown_margin {
+            (Some(Trip::Temperature), Action::Shutdown)
+        } else if temp > self.max_temp {
+            (Some(Trip::Temperature), Action::CutBack)
+        } else if ld_current > self.max_ld_current {
+            (Some(Trip::LdCurrent), Action::CutBack)
+        } else {
+            (None, Action::None)
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! Laser slope-efficiency measurements, for tracking laser aging
+//!
+//! A sweep steps the digipot while reading back the laser and monitor photodiode currents (the
+//! L-I curve). A straight line fit to the part above threshold gives the threshold current and
+//! slope efficiency. Both drift as a laser ages, so each measurement is kept as a
+//! [`SlopeRecord`] along with the module UID and a timestamp, and [`trend`] fits the drift over
+//! a series of them. With `std`, records can be kept in a JSON-lines history file.
+
+use super::ftx::{self, Ftx, FtxResult, LD_SETTLE_MS};
+use embedded_hal::{delay::DelayNs, i2c::I2c};
+use serde::{Deserialize, Serialize};
+
+/// Fraction of the peak monitor current a point needs to count as lasing for the fit
+///
+/// This keeps the knee, where spontaneous emission still dominates, out of the fit.
+const LASING_FRACTION: f32 = 0.2;
+
+/// Seconds per day, the unit of the aging trends
+const SECONDS_PER_DAY: f32 = 86400.0;
+
+/// Digipot range and limits of a slope sweep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweepConfig {
+    /// First wiper word
+    pub start_word: u8,
+    /// Last wiper word (inclusive)
+    pub stop_word: u8,
+    /// Wiper words between points
+    pub step: u8,
+    /// Laser current (in mA) the sweep stops at, on top of the safety policy
+    pub max_ld_current: f32,
+    /// Time to settle after every wiper change (in ms)
+    pub settle_ms: u32,
+}
+
+impl Default for SweepConfig {
+    /// The whole wiper travel in steps of 4, stopping well below the current source limit
+    fn default() -> Self {
+        Self {
+            start_word: 0,
+            stop_word: 255,
+            step: 4,
+            max_ld_current: 40.0,
+            settle_ms: LD_SETTLE_MS,
+        }
+    }
+}
+
+/// One point of the L-I curve
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SweepPoint {
+    /// Digipot wiper word
+    pub word: u8,
+    /// Measured laser current (in mA)
+    pub ld_current: f32,
+    /// Measured monitor photodiode current (in uA)
+    pub pd_current: f32,
+}
+
+/// The measured L-I curve of a sweep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LiCurve {
+    points: [SweepPoint; 256],
+    len: usize,
+}
+
+impl LiCurve {
+    fn new() -> Self {
+        Self {
+            points: [SweepPoint::default(); 256],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, point: SweepPoint) {
+        self.points[self.len] = point;
+        self.len += 1;
+    }
+
+    /// The measured points, in sweep order
+    pub fn points(&self) -> &[SweepPoint] {
+        &self.points[..self.len]
+    }
+
+    /// Fit the threshold and slope efficiency to the lasing part of the curve
+    ///
+    /// Returns `None` if fewer than two points are above threshold, or they don't rise.
+    pub fn fit(&self) -> Option<SlopeFit> {
+        let peak = self
+            .points()
+            .iter()
+            .map(|p| p.pd_current)
+            .fold(0.0, f32::max);
+        let lasing = self
+            .points()
+            .iter()
+            .filter(|p| p.pd_current >= LASING_FRACTION * peak);
+        let (slope, intercept, points) = linear_fit(lasing.map(|p| (p.ld_current, p.pd_current)))?;
+        if slope <= 0.0 {
+            return None;
+        }
+        Some(SlopeFit {
+            threshold: -intercept / slope,
+            slope,
+            points,
+        })
+    }
+}
+
+/// Straight line fit of the lasing part of an L-I curve
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlopeFit {
+    /// Threshold current, where the fit crosses zero (in mA)
+    pub threshold: f32,
+    /// Slope efficiency as monitor current per laser current (in uA/mA)
+    pub slope: f32,
+    /// Number of points in the fit
+    pub points: usize,
+}
+
+/// A slope-efficiency measurement of one module at one time
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SlopeRecord {
+    /// Unique ID of the module
+    pub uid: u64,
+    /// When the measurement was made, supplied by the caller (Unix seconds for [`trend`])
+    pub timestamp: u64,
+    /// Board temperature during the sweep (in C), threshold rises with temperature
+    pub temp_c: f32,
+    /// Threshold current (in mA)
+    pub threshold_ma: f32,
+    /// Slope efficiency as monitor current per laser current (in uA/mA)
+    pub slope_ua_per_ma: f32,
+    /// Slope efficiency as launched power per laser current (in W/A), with an optical calibration
+    pub slope_w_per_a: Option<f32>,
+    /// Number of points in the fit
+    pub points: usize,
+}
+
+/// Least-squares straight line through `(x, y)` points, as `(slope, intercept, points)`
+fn linear_fit(points: impl Iterator<Item = (f32, f32)>) -> Option<(f32, f32, usize)> {
+    let (mut n, mut sx, mut sy, mut sxx, mut sxy) = (0usize, 0.0, 0.0, 0.0, 0.0);
+    for (x, y) in points {
+        n += 1;
+        sx += x;
+        sy += y;
+        sxx += x * x;
+        sxy += x * y;
+    }
+    let nf = n as f32;
+    let denom = nf * sxx - sx * sx;
+    if n < 2 || denom == 0.0 {
+        return None;
+    }
+    let slope = (nf * sxy - sx * sy) / denom;
+    Some((slope, (sy - slope * sx) / nf, n))
+}
+
+/// Drift of a module's slope measurements over time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trend {
+    /// Change in threshold current (in mA/day)
+    pub threshold_ma_per_day: f32,
+    /// Change in slope efficiency (in uA/mA per day)
+    pub slope_per_day: f32,
+    /// Number of records in the fit
+    pub records: usize,
+    /// Time between the first and last record (in days)
+    pub span_days: f32,
+}
+
+/// Fit the drift of one module's records (timestamps in Unix seconds)
+///
+/// Records for other modules are skipped. Returns `None` with fewer than two records at
+/// different times.
+pub fn trend(records: &[SlopeRecord], uid: u64) -> Option<Trend> {
+    let mine = || records.iter().filter(move |r| r.uid == uid);
+    let first = mine().map(|r| r.timestamp).min()?;
+    let last = mine().map(|r| r.timestamp).max()?;
+    // Offset from the first record so the days fit in an f32 without losing resolution
+    let days = |r: &SlopeRecord| (r.timestamp - first) as f32 / SECONDS_PER_DAY;
+    let (threshold_ma_per_day, _, records) = linear_fit(mine().map(|r| (days(r), r.threshold_ma)))?;
+    let (slope_per_day, _, _) = linear_fit(mine().map(|r| (days(r), r.slope_ua_per_ma)))?;
+    Some(Trend {
+        threshold_ma_per_day,
+        slope_per_day,
+        records,
+        span_days: (last - first) as f32 / SECONDS_PER_DAY,
+    })
+}
+
+impl<I2C, E> Ftx<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Sweep the digipot and record the L-I curve
+    ///
+    /// Every setpoint goes through the safety policy, and the sweep stops before any word whose
+    /// expected current (or after any point whose measured current) exceeds
+    /// `config.max_ld_current`. The original wiper word is restored afterwards (through the safety
+    /// policy, so it stays lower if that is no longer safe), even if the sweep failed.
+    pub fn sweep_li<D: DelayNs>(
+        &mut self,
+        config: &SweepConfig,
+        delay: &mut D,
+    ) -> FtxResult<LiCurve, E> {
+        let original = self.ld_word()?;
+        let curve = self.sweep_li_inner(config, delay);
+        let restore = self.set_ld_raw(original);
+        let curve = curve?;
+        restore?;
+        Ok(curve)
+    }
+
+    fn sweep_li_inner<D: DelayNs>(
+        &mut self,
+        config: &SweepConfig,
+        delay: &mut D,
+    ) -> FtxResult<LiCurve, E> {
+        let mut curve = LiCurve::new();
+        let step = config.step.max(1) as usize;
+        for word in (config.start_word..=config.stop_word).step_by(step) {
+            if self.digipot().current(word) > config.max_ld_current {
+                break;
+            }
+            self.set_ld_raw(word)?;
+            delay.delay_ms(config.settle_ms);
+            let ld_current = self.adc.ld_current().map_err(|e| ftx::Error::Adc(e))?;
+            let pd_current = self.adc.pd_current().map_err(|e| ftx::Error::Adc(e))?;
+            curve.push(SweepPoint {
+                word,
+                ld_current,
+                pd_current,
+            });
+            if ld_current > config.max_ld_current {
+                break;
+            }
+        }
+        Ok(curve)
+    }
+
+    /// Sweep the laser and fit its threshold and slope efficiency
+    ///
+    /// `timestamp` is stored as-is in the record, use Unix seconds to get trends from [`trend`].
+    /// The slope in W/A is filled in when there is a monitor photodiode calibration.
+    pub fn measure_slope<D: DelayNs>(
+        &mut self,
+        config: &SweepConfig,
+        timestamp: u64,
+        delay: &mut D,
+    ) -> FtxResult<SlopeRecord, E> {
+        let uid = self.temp.uid()?;
+        let temp_c = self.temp.temp()?;
+        let fit = self
+            .sweep_li(config, delay)?
+            .fit()
+            .ok_or(ftx::Error::NoLasing)?;
+        Ok(SlopeRecord {
+            uid,
+            timestamp,
+            temp_c,
+            threshold_ma: fit.threshold,
+            slope_ua_per_ma: fit.slope,
+            // uA/mA is mA/A, over A/W of responsivity
+            slope_w_per_a: self
+                .optical
+                .map(|cal| fit.slope / 1000.0 / cal.responsivity),
+            points: fit.points,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+pub use history::{append_history, load_history, HistoryError};
+
+#[cfg(feature = "std")]
+mod history {
+    use super::SlopeRecord;
+    use std::{
+        fmt,
+        fs::{File, OpenOptions},
+        io::{BufRead, BufReader, Write},
+        path::Path,
+    };
+
+    /// Errors reading or writing a slope history file
+    #[derive(Debug)]
+    pub enum HistoryError {
+        Io(std::io::Error),
+        /// A record that couldn't be (de)serialized
+        Json(serde_json::Error),
+    }
+
+    impl fmt::Display for HistoryError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                HistoryError::Io(e) => e.fmt(f),
+                HistoryError::Json(e) => write!(f, "bad slope history record: {e}"),
+            }
+        }
+    }
+
+    impl std::error::Error for HistoryError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                HistoryError::Io(e) => Some(e),
+                HistoryError::Json(e) => Some(e),
+            }
+        }
+    }
+
+    impl From<std::io::Error> for HistoryError {
+        fn from(e: std::io::Error) -> Self {
+            HistoryError::Io(e)
+        }
+    }
+
+    impl From<serde_json::Error> for HistoryError {
+        fn from(e: serde_json::Error) -> Self {
+            HistoryError::Json(e)
+        }
+    }
+
+    /// Append a record to a JSON-lines history file, creating it if needed
+    pub fn append_history<P: AsRef<Path>>(
+        path: P,
+        record: &SlopeRecord,
+    ) -> Result<(), HistoryError> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let line = serde_json::to_string(record)?;
+        writeln!(file, "{line}")?;
+        Ok(())
+    }
+
+    /// Read every record from a JSON-lines history file, oldest first
+    pub fn load_history<P: AsRef<Path>>(path: P) -> Result<Vec<SlopeRecord>, HistoryError> {
+        let mut records = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            records.push(serde_json::from_str(&line)?);
+        }
+        Ok(records)
+    }
+}
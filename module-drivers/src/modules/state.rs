@@ -0,0 +1,352 @@
+//! Saving and restoring every controllable setting of a module
+//!
+//! The states are plain serde structs, with `std` they can be written to and read from TOML
+//! or JSON files through [`StateFile`].
+
+use super::{
+    frx::{Frx, FrxResult},
+    ftx::{self, Ftx, FtxResult},
+};
+use crate::peripherals::temp::{self, AlertConfig, TempConfig, TemperataureSensor, SCALE_C};
+use embedded_hal::i2c::I2c;
+use serde::{Deserialize, Serialize};
+
+/// A setting that differs between a saved state and the hardware, as `(saved, actual)`
+pub type Change<T> = Option<(T, T)>;
+
+fn change<T: PartialEq>(saved: T, actual: T) -> Change<T> {
+    (saved != actual).then_some((saved, actual))
+}
+
+// Temperatures only survive a round trip through the sensor to within its resolution
+fn temp_change(saved: f32, actual: f32) -> Change<f32> {
+    ((saved - actual).abs() >= SCALE_C).then_some((saved, actual))
+}
+
+/// Temperature sensor settings
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TempState {
+    /// Conversion settings
+    pub config: TempConfig,
+    /// Alert pin and flag behaviour
+    pub alert: AlertConfig,
+    /// Low temperature limit (in C)
+    pub low_limit: f32,
+    /// High temperature limit (in C)
+    pub high_limit: f32,
+    /// Offset added to every conversion (in C)
+    pub offset: f32,
+}
+
+/// Temperature sensor settings that differ from a saved state
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TempDiff {
+    pub config: Change<TempConfig>,
+    pub alert: Change<AlertConfig>,
+    pub low_limit: Change<f32>,
+    pub high_limit: Change<f32>,
+    pub offset: Change<f32>,
+}
+
+impl TempDiff {
+    /// Whether everything matched
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl TempState {
+    /// Read the settings back from a sensor
+    ///
+    /// This reads the configuration register, which clears the alert flags latched in the
+    /// sensor, the driver keeps them for its next `alert_flags`.
+    pub fn capture<I2C, E>(sensor: &mut TemperataureSensor<I2C>) -> Result<Self, temp::Error<E>>
+    where
+        I2C: I2c<Error = E>,
+        E: embedded_hal::i2c::Error,
+    {
+        let (low_limit, high_limit) = sensor.limits()?;
+        Ok(Self {
+            config: sensor.config()?,
+            alert: sensor.alert_config()?,
+            low_limit,
+            high_limit,
+            offset: sensor.offset()?,
+        })
+    }
+
+    /// Write the settings to a sensor
+    pub fn apply<I2C, E>(&self, sensor: &mut TemperataureSensor<I2C>) -> Result<(), temp::Error<E>>
+    where
+        I2C: I2c<Error = E>,
+        E: embedded_hal::i2c::Error,
+    {
+        sensor.configure(self.config)?;
+        sensor.set_alert_config(self.alert)?;
+        sensor.set_limits(self.low_limit, self.high_limit)?;
+        sensor.set_offset(self.offset)?;
+        Ok(())
+    }
+
+    /// Compare against the settings actually on the hardware
+    pub fn diff(&self, actual: &Self) -> TempDiff {
+        TempDiff {
+            config: change(self.config, actual.config),
+            alert: change(self.alert, actual.alert),
+            low_limit: temp_change(self.low_limit, actual.low_limit),
+            high_limit: temp_change(self.high_limit, actual.high_limit),
+            offset: temp_change(self.offset, actual.offset),
+        }
+    }
+}
+
+/// Every controllable setting of an FTX
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FtxState {
+    /// Attenuation (in dB)
+    pub atten_db: f32,
+    /// Laser digipot word, saved raw so the exact setpoint comes back regardless of calibration
+    pub ld_word: u8,
+    /// LNA bias enable
+    pub lna_enabled: bool,
+    /// Temperature sensor settings
+    pub temp: TempState,
+}
+
+/// FTX settings that differ from a saved state
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FtxDiff {
+    pub atten_db: Change<f32>,
+    pub ld_word: Change<u8>,
+    pub lna_enabled: Change<bool>,
+    pub temp: TempDiff,
+}
+
+impl FtxDiff {
+    /// Whether everything matched
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl FtxState {
+    /// Compare against the settings actually on the hardware
+    pub fn diff(&self, actual: &Self) -> FtxDiff {
+        FtxDiff {
+            atten_db: change(self.atten_db, actual.atten_db),
+            ld_word: change(self.ld_word, actual.ld_word),
+            lna_enabled: change(self.lna_enabled, actual.lna_enabled),
+            temp: self.temp.diff(&actual.temp),
+        }
+    }
+}
+
+impl<I2C, E> Ftx<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Read back every controllable setting
+    pub fn capture_state(&mut self) -> FtxResult<FtxState, E> {
+        Ok(FtxState {
+            atten_db: self.atten.get_db()?,
+            ld_word: self.ld_word()?,
+            lna_enabled: self.adc.lna_enabled().map_err(|e| ftx::Error::Adc(e))?,
+            temp: TempState::capture(&mut self.temp)?,
+        })
+    }
+
+    /// Bring the hardware in line with a saved state
+    ///
+    /// The laser is set last (and checked against the safety policy), after everything it
+    /// depends on.
+    pub fn apply_state(&mut self, state: &FtxState) -> FtxResult<(), E> {
+        state.temp.apply(&mut self.temp)?;
+        self.atten.set_db(state.atten_db)?;
+        self.adc
+            .enable_lna(state.lna_enabled)
+            .map_err(|e| ftx::Error::Adc(e))?;
+        self.set_ld_raw(state.ld_word)?;
+        Ok(())
+    }
+
+    /// Compare a saved state against the hardware
+    pub fn diff_state(&mut self, state: &FtxState) -> FtxResult<FtxDiff, E> {
+        Ok(state.diff(&self.capture_state()?))
+    }
+}
+
+/// Every controllable setting of an FRX
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FrxState {
+    /// Attenuation (in dB)
+    pub atten_db: f32,
+    /// Temperature sensor settings
+    pub temp: TempState,
+}
+
+/// FRX settings that differ from a saved state
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FrxDiff {
+    pub atten_db: Change<f32>,
+    pub temp: TempDiff,
+}
+
+impl FrxDiff {
+    /// Whether everything matched
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl FrxState {
+    /// Compare against the settings actually on the hardware
+    pub fn diff(&self, actual: &Self) -> FrxDiff {
+        FrxDiff {
+            atten_db: change(self.atten_db, actual.atten_db),
+            temp: self.temp.diff(&actual.temp),
+        }
+    }
+}
+
+impl<I2C, E> Frx<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Read back every controllable setting
+    pub fn capture_state(&mut self) -> FrxResult<FrxState, E> {
+        Ok(FrxState {
+            atten_db: self.atten.get_db()?,
+            temp: TempState::capture(&mut self.temp)?,
+        })
+    }
+
+    /// Bring the hardware in line with a saved state
+    pub fn apply_state(&mut self, state: &FrxState) -> FrxResult<(), E> {
+        state.temp.apply(&mut self.temp)?;
+        self.atten.set_db(state.atten_db)?;
+        Ok(())
+    }
+
+    /// Compare a saved state against the hardware
+    pub fn diff_state(&mut self, state: &FrxState) -> FrxResult<FrxDiff, E> {
+        Ok(state.diff(&self.capture_state()?))
+    }
+}
+
+#[cfg(feature = "std")]
+pub use file::{StateFile, StateFileError};
+
+#[cfg(feature = "std")]
+mod file {
+    use super::{FrxState, FtxState};
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::{fmt, fs, path::Path};
+
+    /// Errors reading or writing a state file
+    #[derive(Debug)]
+    pub enum StateFileError {
+        Io(std::io::Error),
+        TomlSer(toml::ser::Error),
+        TomlDe(toml::de::Error),
+        Json(serde_json::Error),
+        /// The file extension wasn't `.toml` or `.json`
+        UnknownFormat,
+    }
+
+    impl fmt::Display for StateFileError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                StateFileError::Io(e) => e.fmt(f),
+                StateFileError::TomlSer(e) => e.fmt(f),
+                StateFileError::TomlDe(e) => e.fmt(f),
+                StateFileError::Json(e) => e.fmt(f),
+                StateFileError::UnknownFormat => {
+                    write!(f, "unknown state file format, expected .toml or .json")
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for StateFileError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                StateFileError::Io(e) => Some(e),
+                StateFileError::TomlSer(e) => Some(e),
+                StateFileError::TomlDe(e) => Some(e),
+                StateFileError::Json(e) => Some(e),
+                StateFileError::UnknownFormat => None,
+            }
+        }
+    }
+
+    impl From<std::io::Error> for StateFileError {
+        fn from(e: std::io::Error) -> Self {
+            StateFileError::Io(e)
+        }
+    }
+
+    impl From<toml::ser::Error> for StateFileError {
+        fn from(e: toml::ser::Error) -> Self {
+            StateFileError::TomlSer(e)
+        }
+    }
+
+    impl From<toml::de::Error> for StateFileError {
+        fn from(e: toml::de::Error) -> Self {
+            StateFileError::TomlDe(e)
+        }
+    }
+
+    impl From<serde_json::Error> for StateFileError {
+        fn from(e: serde_json::Error) -> Self {
+            StateFileError::Json(e)
+        }
+    }
+
+    /// TOML and JSON (de)serialization of a saved state
+    pub trait StateFile: Serialize + DeserializeOwned {
+        fn to_toml(&self) -> Result<String, StateFileError> {
+            Ok(toml::to_string_pretty(self)?)
+        }
+
+        fn from_toml(s: &str) -> Result<Self, StateFileError> {
+            Ok(toml::from_str(s)?)
+        }
+
+        fn to_json(&self) -> Result<String, StateFileError> {
+            Ok(serde_json::to_string_pretty(self)?)
+        }
+
+        fn from_json(s: &str) -> Result<Self, StateFileError> {
+            Ok(serde_json::from_str(s)?)
+        }
+
+        /// Write to a file, picking the format from the `.toml` or `.json` extension
+        fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), StateFileError> {
+            let path = path.as_ref();
+            let s = match path.extension().and_then(|e| e.to_str()) {
+                Some("toml") => self.to_toml()?,
+                Some("json") => self.to_json()?,
+                _ => return Err(StateFileError::UnknownFormat),
+            };
+            fs::write(path, s)?;
+            Ok(())
+        }
+
+        /// Read from a file, picking the format from the `.toml` or `.json` extension
+        fn load<P: AsRef<Path>>(path: P) -> Result<Self, StateFileError> {
+            let path = path.as_ref();
+            let s = fs::read_to_string(path)?;
+            match path.extension().and_then(|e| e.to_str()) {
+                Some("toml") => Self::from_toml(&s),
+                Some("json") => Self::from_json(&s),
+                _ => Err(StateFileError::UnknownFormat),
+            }
+        }
+    }
+
+    impl StateFile for FtxState {}
+    impl StateFile for FrxState {}
+}
@@ -1,5 +1,7 @@
 //! A dumbed-down driver of the TLA2528
 
+use super::Error;
+use crate::peripherals::{BusError, Chip};
 use embedded_hal::i2c::I2c;
 
 #[repr(u8)]
@@ -43,43 +45,51 @@ where
         Self { bus, addr }
     }
 
-    #[allow(unused)]
-    fn read_raw_reg(&mut self, reg: u8) -> Result<u8, E> {
+    fn err(&self, op: &'static str) -> impl FnOnce(E) -> Error<E> {
+        BusError::map(Chip::Tla2528, self.addr, op)
+    }
+
+    fn read_raw_reg(&mut self, reg: u8) -> Result<u8, Error<E>> {
         let mut byte = [0u8];
         self.bus
-            .write_read(self.addr, &[Op::SingleRegRead as u8, reg], &mut byte)?;
+            .write_read(self.addr, &[Op::SingleRegRead as u8, reg], &mut byte)
+            .map_err(self.err("register read"))?;
         Ok(byte[0])
     }
 
-    #[allow(unused)]
-    fn read_reg(&mut self, reg: Reg) -> Result<u8, E> {
+    fn read_reg(&mut self, reg: Reg) -> Result<u8, Error<E>> {
         self.read_raw_reg(reg as u8)
     }
 
-    fn set_bit(&mut self, reg: Reg, bit: u8) -> Result<(), E> {
+    fn set_bit(&mut self, reg: Reg, bit: u8) -> Result<(), Error<E>> {
         self.bus
-            .write(self.addr, &[Op::SetBit as u8, reg as u8, 1 << bit])?;
+            .write(self.addr, &[Op::SetBit as u8, reg as u8, 1 << bit])
+            .map_err(self.err("register bit set"))?;
         Ok(())
     }
 
-    fn clear_bit(&mut self, reg: Reg, bit: u8) -> Result<(), E> {
+    fn clear_bit(&mut self, reg: Reg, bit: u8) -> Result<(), Error<E>> {
         self.bus
-            .write(self.addr, &[Op::ClearBit as u8, reg as u8, 1 << bit])?;
+            .write(self.addr, &[Op::ClearBit as u8, reg as u8, 1 << bit])
+            .map_err(self.err("register bit clear"))?;
         Ok(())
     }
 
-    fn write_reg(&mut self, reg: Reg, byte: u8) -> Result<(), E> {
+    fn write_reg(&mut self, reg: Reg, byte: u8) -> Result<(), Error<E>> {
         self.bus
-            .write(self.addr, &[Op::SingleRegWrite as u8, reg as u8, byte])?;
+            .write(self.addr, &[Op::SingleRegWrite as u8, reg as u8, byte])
+            .map_err(self.err("register write"))?;
         Ok(())
     }
 
     /// Will attempt to read up to `n` averages (anything greater than 256 will default to 256)
-    fn read_and_average(&mut self, n: usize) -> Result<u16, E> {
+    fn read_and_average(&mut self, n: usize) -> Result<u16, Error<E>> {
         let mut bytes = [0u8; 512];
         let trunc_n = if n <= 256 { n } else { 256 };
         let slice = &mut bytes[0..(trunc_n * 2)];
-        self.bus.read(self.addr, slice)?;
+        self.bus
+            .read(self.addr, slice)
+            .map_err(self.err("conversion read"))?;
         let mut res = [0u16; 256];
         slice.chunks(2).enumerate().for_each(|(i, chunk)| {
             res[i] = u16::from_be_bytes(chunk.try_into().unwrap()) >> 4;
@@ -88,17 +98,17 @@ where
     }
 
     // ---- Higher-level stuff
-    pub fn reset(&mut self) -> Result<(), E> {
+    pub fn reset(&mut self) -> Result<(), Error<E>> {
         self.set_bit(Reg::GeneralCfg, 0)?;
         Ok(())
     }
 
-    pub fn calibrate(&mut self) -> Result<(), E> {
+    pub fn calibrate(&mut self) -> Result<(), Error<E>> {
         self.set_bit(Reg::SystemStatus, 0)?;
         Ok(())
     }
 
-    pub fn set_pin_mode(&mut self, mode: PinMode, chan: u8) -> Result<(), E> {
+    pub fn set_pin_mode(&mut self, mode: PinMode, chan: u8) -> Result<(), Error<E>> {
         match mode {
             PinMode::Analog => {
                 self.clear_bit(Reg::PinCfg, chan)?; // Analog
@@ -112,19 +122,36 @@ where
         Ok(())
     }
 
+    /// Check whether a channel is already set up in the given mode
+    pub fn pin_mode_is(&mut self, mode: PinMode, chan: u8) -> Result<bool, Error<E>> {
+        let bit = |reg: u8| reg & (1 << chan) != 0;
+        let gpio = bit(self.read_reg(Reg::PinCfg)?);
+        Ok(match mode {
+            PinMode::Analog => !gpio,
+            PinMode::DigitalOut => {
+                gpio && bit(self.read_reg(Reg::GpioCfg)?) && bit(self.read_reg(Reg::GpoDriveCfg)?)
+            }
+        })
+    }
+
     /// Undefined behavior happens on pins that aren't analog (perhaps we should check)
-    pub fn read_chan_with_average(&mut self, chan: u8, avgs: usize) -> Result<u16, E> {
+    pub fn read_chan_with_average(&mut self, chan: u8, avgs: usize) -> Result<u16, Error<E>> {
         self.write_reg(Reg::ChannelSel, chan)?;
         self.read_and_average(avgs)
     }
 
-    pub fn digital_write(&mut self, chan: u8, set: bool) -> Result<(), E> {
+    pub fn digital_write(&mut self, chan: u8, set: bool) -> Result<(), Error<E>> {
         if set {
             self.set_bit(Reg::GpoValue, chan)
         } else {
             self.clear_bit(Reg::GpoValue, chan)
         }
     }
+
+    /// Read back the level a digital output is being driven to
+    pub fn digital_state(&mut self, chan: u8) -> Result<bool, Error<E>> {
+        Ok(self.read_reg(Reg::GpoValue)? & (1 << chan) != 0)
+    }
 }
 
 /// Neat algo for integer averaging without overflow
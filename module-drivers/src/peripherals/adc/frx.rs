@@ -1,7 +1,7 @@
 //! FRX-Specific ADC implementation
 
 use super::driver::{Adc as RawAdc, PinMode};
-use super::{Adc as AdcTrait, RF_AVGS, VOLTAGE_AVGS};
+use super::{Adc as AdcTrait, Error, RF_AVGS, VOLTAGE_AVGS};
 use embedded_hal::i2c::I2c;
 
 /// High-level ADC interface for the FRX
@@ -30,19 +30,22 @@ where
     }
 }
 
-/// The meat of the implementation
+// The meat of the implementation
 
 /// Photodiode DC current monitor resistor value
-const PDI_SHUNT: f32 = 5.1;
+pub(crate) const PDI_SHUNT: f32 = 5.1;
 
 /// Current-sense amplifier gain
-const GAIN: f32 = 100.0;
+pub(crate) const GAIN: f32 = 100.0;
 
 /// RF power monitoring channel
-const RF: u8 = 0;
+pub(crate) const RF: u8 = 0;
 
 /// Photodiode current monitoring channel
-const PDI: u8 = 1;
+pub(crate) const PDI: u8 = 1;
+
+/// Pin configuration
+const PINS: [(u8, PinMode); 2] = [(RF, PinMode::Analog), (PDI, PinMode::Analog)];
 
 impl<I2C, E> Adc<I2C>
 where
@@ -50,20 +53,25 @@ where
     E: embedded_hal::i2c::Error,
 {
     /// Initialize and setup the ADC
-    pub fn init(&mut self) -> Result<(), E> {
+    pub fn init(&mut self) -> Result<(), Error<E>> {
         self.0.reset()?;
         self.0.calibrate()?;
-        self.configure(&[(RF, PinMode::Analog), (PDI, PinMode::Analog)])?;
+        self.configure(&PINS)?;
         Ok(())
     }
 
+    /// Check (without writing anything) whether the ADC has already been set up by `init`
+    pub fn is_initialized(&mut self) -> Result<bool, Error<E>> {
+        self.is_configured(&PINS)
+    }
+
     /// Get the DC photodiode current (in mA)
-    pub fn pd_current(&mut self) -> Result<f32, E> {
+    pub fn pd_current(&mut self) -> Result<f32, Error<E>> {
         Ok(self.read_current_avgs(PDI, PDI_SHUNT, GAIN, VOLTAGE_AVGS)? * 1000.0)
     }
 
     /// Get the RF power (in dBm)
-    pub fn rf_power(&mut self) -> Result<f32, E> {
+    pub fn rf_power(&mut self) -> Result<f32, Error<E>> {
         let raw = self.read_float_avgs(RF, RF_AVGS)?;
         Ok(17.74 * (raw * 5.0) - 55.0)
     }
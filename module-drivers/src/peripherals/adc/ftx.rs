@@ -1,7 +1,7 @@
 //! FTX-Specific ADC implementation
 
 use super::driver::{Adc as RawAdc, PinMode};
-use super::{Adc as AdcTrait, RF_AVGS, VOLTAGE_AVGS};
+use super::{Adc as AdcTrait, Error, RF_AVGS, VOLTAGE_AVGS};
 use embedded_hal::i2c::I2c;
 
 /// High-level ADC interface for the FTX
@@ -30,30 +30,42 @@ where
     }
 }
 
-/// The meat of the implementation
+// The meat of the implementation
 
 // Gains and scaling factors
-const LDI_SHUNT: f32 = 1.0;
-const PDI_SHUNT: f32 = 100.0;
-const LNAI_SHUNT: f32 = 0.5;
+pub(crate) const LDI_SHUNT: f32 = 1.0;
+pub(crate) const PDI_SHUNT: f32 = 100.0;
+pub(crate) const LNAI_SHUNT: f32 = 0.5;
 
-const LDI_GAIN: f32 = 100.0;
-const PDI_GAIN: f32 = 100.0;
-const LNAI_GAIN: f32 = 100.0;
+pub(crate) const LDI_GAIN: f32 = 100.0;
+pub(crate) const PDI_GAIN: f32 = 100.0;
+pub(crate) const LNAI_GAIN: f32 = 100.0;
 
-const VDDA_GAIN: f32 = 0.5;
-const VLNA_GAIN: f32 = 0.25;
-const VDD_GAIN: f32 = 0.5;
+pub(crate) const VDDA_GAIN: f32 = 0.5;
+pub(crate) const VLNA_GAIN: f32 = 0.25;
+pub(crate) const VDD_GAIN: f32 = 0.5;
 
 // Channels
-const VDDA: u8 = 0;
-const PDI: u8 = 1;
-const RF: u8 = 2;
-const LNAI: u8 = 3;
-const LDI: u8 = 4;
-const VLNA: u8 = 5;
-const LNA_EN: u8 = 6;
-const VDD: u8 = 7;
+pub(crate) const VDDA: u8 = 0;
+pub(crate) const PDI: u8 = 1;
+pub(crate) const RF: u8 = 2;
+pub(crate) const LNAI: u8 = 3;
+pub(crate) const LDI: u8 = 4;
+pub(crate) const VLNA: u8 = 5;
+pub(crate) const LNA_EN: u8 = 6;
+pub(crate) const VDD: u8 = 7;
+
+/// Pin configuration
+const PINS: [(u8, PinMode); 8] = [
+    (VDDA, PinMode::Analog),
+    (PDI, PinMode::Analog),
+    (RF, PinMode::Analog),
+    (LNAI, PinMode::Analog),
+    (LDI, PinMode::Analog),
+    (VLNA, PinMode::Analog),
+    (LNA_EN, PinMode::DigitalOut),
+    (VDD, PinMode::Analog),
+];
 
 impl<I2C, E> Adc<I2C>
 where
@@ -61,60 +73,61 @@ where
     E: embedded_hal::i2c::Error,
 {
     /// Initialize and setup the ADC
-    pub fn init(&mut self) -> Result<(), E> {
+    pub fn init(&mut self) -> Result<(), Error<E>> {
         self.0.reset()?;
         self.0.calibrate()?;
-        self.configure(&[
-            (VDDA, PinMode::Analog),
-            (PDI, PinMode::Analog),
-            (RF, PinMode::Analog),
-            (LNAI, PinMode::Analog),
-            (LDI, PinMode::Analog),
-            (VLNA, PinMode::Analog),
-            (LNA_EN, PinMode::DigitalOut),
-            (VDD, PinMode::Analog),
-        ])?;
+        self.configure(&PINS)?;
         Ok(())
     }
 
+    /// Check (without writing anything) whether the ADC has already been set up by `init`
+    pub fn is_initialized(&mut self) -> Result<bool, Error<E>> {
+        self.is_configured(&PINS)
+    }
+
     /// Get the analog supply (VDDA) voltage (in V)
-    pub fn analog_voltage(&mut self) -> Result<f32, E> {
+    pub fn analog_voltage(&mut self) -> Result<f32, Error<E>> {
         self.read_voltage_avgs(VDDA, VDDA_GAIN, VOLTAGE_AVGS)
     }
 
     /// Get the DC monitor photodiode current (in uA)
-    pub fn pd_current(&mut self) -> Result<f32, E> {
+    pub fn pd_current(&mut self) -> Result<f32, Error<E>> {
         Ok(self.read_current_avgs(PDI, PDI_SHUNT, PDI_GAIN, VOLTAGE_AVGS)? * 1e6)
     }
 
     /// Get the RF power (in dBm)
-    pub fn rf_power(&mut self) -> Result<f32, E> {
+    pub fn rf_power(&mut self) -> Result<f32, Error<E>> {
         let raw = self.read_float_avgs(RF, RF_AVGS)?;
         Ok(17.74 * (raw * 5.0) - 55.0)
     }
 
     /// Get the LNA current (in mA)
-    pub fn lna_current(&mut self) -> Result<f32, E> {
+    pub fn lna_current(&mut self) -> Result<f32, Error<E>> {
         Ok(self.read_current_avgs(LNAI, LNAI_SHUNT, LNAI_GAIN, VOLTAGE_AVGS)? * 1000.0)
     }
 
     /// Get the DC monitor photodiode current (in mA)
-    pub fn ld_current(&mut self) -> Result<f32, E> {
+    pub fn ld_current(&mut self) -> Result<f32, Error<E>> {
         Ok(self.read_current_avgs(LDI, LDI_SHUNT, LDI_GAIN, VOLTAGE_AVGS)? * 1000.0)
     }
 
     /// Get the LNA voltage (in V)
-    pub fn lna_voltage(&mut self) -> Result<f32, E> {
+    pub fn lna_voltage(&mut self) -> Result<f32, Error<E>> {
         self.read_voltage_avgs(VLNA, VLNA_GAIN, VOLTAGE_AVGS)
     }
 
     /// Get the digital supply (VDD) voltage (in V)
-    pub fn digital_voltage(&mut self) -> Result<f32, E> {
+    pub fn digital_voltage(&mut self) -> Result<f32, Error<E>> {
         self.read_voltage_avgs(VDD, VDD_GAIN, VOLTAGE_AVGS)
     }
 
     /// Set the state of the LNA bias
-    pub fn enable_lna(&mut self, enable: bool) -> Result<(), E> {
+    pub fn enable_lna(&mut self, enable: bool) -> Result<(), Error<E>> {
         self.0.digital_write(LNA_EN, enable)
     }
+
+    /// Whether the LNA bias is enabled
+    pub fn lna_enabled(&mut self) -> Result<bool, Error<E>> {
+        self.0.digital_state(LNA_EN)
+    }
 }
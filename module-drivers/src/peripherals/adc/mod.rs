@@ -9,6 +9,9 @@ const VOLTAGE_AVGS: usize = 64;
 
 use embedded_hal::i2c::I2c;
 
+/// ADC errors are bus errors with the ADC's context
+pub type Error<E> = super::BusError<E>;
+
 pub trait Adc<I2C, E>
 where
     I2C: I2c<Error = E>,
@@ -20,15 +23,25 @@ where
     fn inner_mut(&mut self) -> &mut driver::Adc<I2C>;
 
     /// Configure the ADC given a vector of channel/mode pairs
-    fn configure(&mut self, pin_cfgs: &[(u8, driver::PinMode)]) -> Result<(), E> {
+    fn configure(&mut self, pin_cfgs: &[(u8, driver::PinMode)]) -> Result<(), Error<E>> {
         for (chan, mode) in pin_cfgs {
             self.inner_mut().set_pin_mode(*mode, *chan)?;
         }
         Ok(())
     }
 
+    /// Check whether the ADC is already configured with a vector of channel/mode pairs
+    fn is_configured(&mut self, pin_cfgs: &[(u8, driver::PinMode)]) -> Result<bool, Error<E>> {
+        for (chan, mode) in pin_cfgs {
+            if !self.inner_mut().pin_mode_is(*mode, *chan)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
     /// Read an analog channel as a value from 0 to 1
-    fn read_float_avgs(&mut self, chan: u8, avgs: usize) -> Result<f32, E> {
+    fn read_float_avgs(&mut self, chan: u8, avgs: usize) -> Result<f32, Error<E>> {
         Ok(self.inner_mut().read_chan_with_average(chan, avgs)? as f32 / 4095.0)
     }
 
@@ -39,13 +52,13 @@ where
         shunt: f32,
         gain: f32,
         avgs: usize,
-    ) -> Result<f32, E> {
+    ) -> Result<f32, Error<E>> {
         let raw: f32 = self.read_float_avgs(chan, avgs)?;
         Ok((raw * Self::VREF) / (gain * shunt))
     }
 
     /// Read a voltage-channel given a 'gain' implemented via an amplifier or resistor divider
-    fn read_voltage_avgs(&mut self, chan: u8, gain: f32, avgs: usize) -> Result<f32, E> {
+    fn read_voltage_avgs(&mut self, chan: u8, gain: f32, avgs: usize) -> Result<f32, Error<E>> {
         let raw: f32 = self.read_float_avgs(chan, avgs)?;
         Ok((raw * Self::VREF) / gain)
     }
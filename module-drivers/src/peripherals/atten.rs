@@ -2,6 +2,8 @@
 //! driver to only operate in output mode to control our digital
 //! attenuator.
 
+use super::{BusError, Chip};
+use core::fmt;
 use embedded_hal::i2c::I2c;
 
 const ADDR_PREAMBLE: u8 = 0b0100000;
@@ -32,26 +34,42 @@ where
         }
     }
 
+    fn err(&self, op: &'static str) -> impl FnOnce(E) -> BusError<E> {
+        BusError::map(Chip::Tca6408a, self.addr, op)
+    }
+
     /// Set all pins to outputs
-    fn configure_outputs(&mut self) -> Result<(), E> {
+    fn configure_outputs(&mut self) -> Result<(), BusError<E>> {
         // Write to the configuration register `0` to set to output
         self.bus
-            .write(self.addr, &[Register::Configuration as u8, 0])?;
+            .write(self.addr, &[Register::Configuration as u8, 0])
+            .map_err(self.err("write configuration register"))?;
         Ok(())
     }
 
+    /// Whether all pins are configured as outputs
+    fn outputs_configured(&mut self) -> Result<bool, BusError<E>> {
+        let mut byte = [0u8; 1];
+        self.bus
+            .write_read(self.addr, &[Register::Configuration as u8], &mut byte)
+            .map_err(self.err("read configuration register"))?;
+        Ok(byte[0] == 0)
+    }
+
     /// Write a word to the bus expander
-    fn write_word(&mut self, word: u8) -> Result<(), E> {
+    fn write_word(&mut self, word: u8) -> Result<(), BusError<E>> {
         self.bus
-            .write(self.addr, &[Register::OutputPort as u8, word])?;
+            .write(self.addr, &[Register::OutputPort as u8, word])
+            .map_err(self.err("write output port"))?;
         Ok(())
     }
 
     /// Read a word from the bus expander
-    fn read_word(&mut self) -> Result<u8, E> {
+    fn read_word(&mut self) -> Result<u8, BusError<E>> {
         let mut byte = [0u8; 1];
         self.bus
-            .write_read(self.addr, &[Register::OutputPort as u8], &mut byte)?;
+            .write_read(self.addr, &[Register::OutputPort as u8], &mut byte)
+            .map_err(self.err("read output port"))?;
         Ok(byte[0])
     }
 }
@@ -62,7 +80,7 @@ pub struct Attenuator<I2C>(Tca6408A<I2C>);
 macro_rules! attenuation_variants {
     ($($value:ident),*) => {
         #[repr(u8)]
-        #[derive(Debug)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
         /// Valid attenuation values for the F1958 digital step attentuator
         pub enum Attenuation {
             $(
@@ -95,22 +113,74 @@ macro_rules! generate_attenuation_enum {
 
 generate_attenuation_enum!();
 
+/// Attenuation step size in dB
+pub const STEP_DB: f32 = 0.25;
+
+impl Attenuation {
+    /// Maximum attenuation in dB
+    pub const MAX_DB: f32 = 31.75;
+
+    /// The attenuation state for a value in dB, rounding down to the nearest step
+    pub fn from_db(db: f32) -> Option<Self> {
+        if !(0.0..=Self::MAX_DB).contains(&db) {
+            return None;
+        }
+        let word = (db / STEP_DB) as u8;
+        // Safety: Every value from 0-127 is a valid enum vaue
+        Some(unsafe { core::mem::transmute::<u8, Attenuation>(word & 127) })
+    }
+
+    /// The attenuation in dB
+    pub fn to_db(self) -> f32 {
+        self as u8 as f32 * STEP_DB
+    }
+}
+
 #[derive(Debug)]
 pub enum Error<E> {
     /// Lower level bus error
-    I2c(E),
+    I2c(BusError<E>),
+    /// Requested attenuation was out of range
+    OutOfRange,
 }
 
 // Convert I2C errors into our higher-level error
-impl<E> core::convert::From<E> for Error<E>
+impl<E> core::convert::From<BusError<E>> for Error<E>
 where
     E: embedded_hal::i2c::Error,
 {
-    fn from(value: E) -> Self {
+    fn from(value: BusError<E>) -> Self {
         Error::I2c(value)
     }
 }
 
+impl<E> fmt::Display for Error<E>
+where
+    E: embedded_hal::i2c::Error,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::I2c(e) => e.fmt(f),
+            Error::OutOfRange => {
+                write!(f, "attenuation out of range (0-{} dB)", Attenuation::MAX_DB)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for Error<E>
+where
+    E: embedded_hal::i2c::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::I2c(e) => Some(e),
+            Error::OutOfRange => None,
+        }
+    }
+}
+
 impl<I2C, E> Attenuator<I2C>
 where
     I2C: I2c<Error = E>,
@@ -128,6 +198,11 @@ where
         Ok(())
     }
 
+    /// Check (without writing anything) whether the outputs have already been set up
+    pub fn is_configured(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.0.outputs_configured()?)
+    }
+
     /// Sets the raw attenuation word
     pub fn set_raw(&mut self, atten: u8) -> Result<(), Error<E>> {
         // Reminder: The LE pin (bit 8) needs to be set high
@@ -146,4 +221,15 @@ where
         // Safety: Every value from 0-127 is a valid enum vaue
         Ok(unsafe { core::mem::transmute::<u8, Attenuation>(word & 127) })
     }
+
+    /// Sets the attenuation in dB (0-31.75), rounding down to the nearest 0.25 dB step
+    pub fn set_db(&mut self, db: f32) -> Result<(), Error<E>> {
+        let atten = Attenuation::from_db(db).ok_or(Error::OutOfRange)?;
+        self.set(atten)
+    }
+
+    /// Gets the attenuation in dB
+    pub fn get_db(&mut self) -> Result<f32, Error<E>> {
+        Ok(self.get()?.to_db())
+    }
 }
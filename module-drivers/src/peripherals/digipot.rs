@@ -1,5 +1,7 @@
 //! Stripped-down implementation of the digipot laser current control
 
+use super::{BusError, Chip};
+use core::fmt;
 use embedded_hal::i2c::I2c;
 
 const ADDR_BASE: u8 = 0b0101100;
@@ -23,66 +25,207 @@ where
         }
     }
 
-    fn set_state(&mut self, word: u8) -> Result<(), E> {
+    fn err(&self, op: &'static str) -> impl FnOnce(E) -> BusError<E> {
+        BusError::map(Chip::Cat5171, self.addr, op)
+    }
+
+    fn set_state(&mut self, word: u8) -> Result<(), BusError<E>> {
         // We don't care about reset to midscale or shutdown, so hard-code
         // the "instruction byte" to 0
-        self.bus.write(self.addr, &[0, word])
+        self.bus
+            .write(self.addr, &[0, word])
+            .map_err(self.err("write wiper"))
     }
 
-    fn get_state(&mut self) -> Result<u8, E> {
+    fn get_state(&mut self) -> Result<u8, BusError<E>> {
         let mut byte = [0u8; 1];
-        self.bus.read(self.addr, &mut byte)?;
+        self.bus
+            .read(self.addr, &mut byte)
+            .map_err(self.err("read wiper"))?;
         Ok(byte[0])
     }
 }
 
+/// Nominal laser current (in mA) per wiper word, a linear 0-50 mA over the 256 words
+const NOMINAL_MA_PER_WORD: f32 = 50.0 / 255.0;
+
+/// Measured transfer curve of the laser current source
+///
+/// The wiper to current mapping is not linear, so we store the measured laser current (in mA)
+/// for the wiper words from 0 up. A curve can stop short of word 255 (a calibration stops at the
+/// laser current limit), the words past its end carry on along its last step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    curve: [f32; 256],
+    len: usize,
+}
+
+impl Calibration {
+    /// From the measured currents (in mA) of wiper words 0 up, `None` unless there are 1 to 256
+    /// of them and they are all finite
+    pub fn new(points: &[f32]) -> Option<Self> {
+        if points.is_empty() || points.len() > 256 || !points.iter().all(|p| p.is_finite()) {
+            return None;
+        }
+        let mut curve = [0.0; 256];
+        curve[..points.len()].copy_from_slice(points);
+        Some(Self {
+            curve,
+            len: points.len(),
+        })
+    }
+
+    /// The measured currents (in mA), from wiper word 0 up
+    pub fn points(&self) -> &[f32] {
+        &self.curve[..self.len]
+    }
+
+    /// The measured current (in mA) for a given wiper word, extrapolated past the end of the curve
+    pub fn current(&self, word: u8) -> f32 {
+        let word = word as usize;
+        if word < self.len {
+            return self.curve[word];
+        }
+        let last = self.curve[self.len - 1];
+        // Never assume the current levels off, the words past the end are above the last point
+        let step = match self.len {
+            1 => NOMINAL_MA_PER_WORD,
+            _ => (last - self.curve[self.len - 2]).max(NOMINAL_MA_PER_WORD),
+        };
+        last + step * (word + 1 - self.len) as f32
+    }
+
+    /// Inverse lookup, the measured wiper word whose current is closest to `current` (in mA)
+    pub fn word(&self, current: f32) -> u8 {
+        let mut best = 0;
+        let mut best_err = f32::INFINITY;
+        for (word, measured) in self.points().iter().enumerate() {
+            let err = if *measured > current {
+                *measured - current
+            } else {
+                current - *measured
+            };
+            if err < best_err {
+                best = word;
+                best_err = err;
+            }
+        }
+        best as u8
+    }
+}
+
 /// High-level laser current control struct
-pub struct Digipot<I2C>(Cat5171<I2C>);
+pub struct Digipot<I2C> {
+    pot: Cat5171<I2C>,
+    cal: Option<Calibration>,
+}
 
 #[derive(Debug)]
 pub enum Error<E> {
     /// Lower level bus error
-    I2c(E),
+    I2c(BusError<E>),
     /// Requested current was out of range
     OutOfRange,
 }
 
 // Convert I2C errors into our higher-level error
-impl<E> core::convert::From<E> for Error<E>
+impl<E> core::convert::From<BusError<E>> for Error<E>
 where
     E: embedded_hal::i2c::Error,
 {
-    fn from(value: E) -> Self {
+    fn from(value: BusError<E>) -> Self {
         Error::I2c(value)
     }
 }
 
+impl<E> fmt::Display for Error<E>
+where
+    E: embedded_hal::i2c::Error,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::I2c(e) => e.fmt(f),
+            Error::OutOfRange => write!(f, "laser current out of range (0-50 mA)"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for Error<E>
+where
+    E: embedded_hal::i2c::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::I2c(e) => Some(e),
+            Error::OutOfRange => None,
+        }
+    }
+}
+
 impl<I2C, E> Digipot<I2C>
 where
     I2C: I2c<Error = E>,
     E: embedded_hal::i2c::Error,
 {
     pub fn new(bus: I2C, ad0: bool) -> Self {
-        Self(Cat5171::new(bus, ad0))
+        Self {
+            pot: Cat5171::new(bus, ad0),
+            cal: None,
+        }
+    }
+
+    /// Use a measured transfer curve for the mA conversions in `set` and `get`
+    pub fn set_calibration(&mut self, cal: Calibration) {
+        self.cal = Some(cal);
+    }
+
+    /// Go back to the nominal (linear) mA conversions
+    pub fn clear_calibration(&mut self) {
+        self.cal = None;
+    }
+
+    /// The transfer curve in use, if any
+    pub fn calibration(&self) -> Option<&Calibration> {
+        self.cal.as_ref()
     }
 
     pub fn set_raw(&mut self, word: u8) -> Result<(), Error<E>> {
-        Ok(self.0.set_state(word)?)
+        Ok(self.pot.set_state(word)?)
+    }
+
+    /// Gets the raw 256-position wiper word
+    pub fn get_raw(&mut self) -> Result<u8, Error<E>> {
+        Ok(self.pot.get_state()?)
     }
 
     /// Set the laser current source in mA
-    /// This function will approximate the closest to the appropriate 256-bit word
+    /// This function will approximate the closest to the appropriate 256-bit word,
+    /// using the measured transfer curve if we have one
     pub fn set(&mut self, current: f32) -> Result<(), Error<E>> {
         if !(0.0..=50.0).contains(&current) {
             return Err(Error::OutOfRange);
         }
-        let raw = (current * 255.0 / 50.0) as u8;
+        let raw = match &self.cal {
+            Some(cal) => cal.word(current),
+            None => (current / NOMINAL_MA_PER_WORD) as u8,
+        };
         self.set_raw(raw)
     }
 
     /// Gets the state of the adjustable current soruce in mA
     pub fn get(&mut self) -> Result<f32, Error<E>> {
-        let raw = self.0.get_state()?;
-        Ok(raw as f32 * 50.0 / 255.0)
+        let raw = self.pot.get_state()?;
+        Ok(self.current(raw))
+    }
+
+    /// The current (in mA) a given wiper word sets
+    ///
+    /// Uses the measured transfer curve if we have one, the nominal mapping otherwise.
+    pub fn current(&self, word: u8) -> f32 {
+        match &self.cal {
+            Some(cal) => cal.current(word),
+            None => word as f32 * NOMINAL_MA_PER_WORD,
+        }
     }
 }
@@ -2,3 +2,85 @@ pub mod adc;
 pub mod atten;
 pub mod digipot;
 pub mod temp;
+
+use core::fmt;
+use embedded_hal::i2c::ErrorKind;
+
+/// The chips we expect to find on a module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip {
+    /// TMP117 temperature sensor / ID
+    Tmp117,
+    /// TLA2528 monitor ADC
+    Tla2528,
+    /// TCA6408A bus expander driving the attenuator
+    Tca6408a,
+    /// CAT5171 digipot setting the laser current (FTX only)
+    Cat5171,
+}
+
+impl Chip {
+    /// Human-readable name of the chip and what it does on the module
+    pub fn name(&self) -> &'static str {
+        match self {
+            Chip::Tmp117 => "TMP117 temperature sensor",
+            Chip::Tla2528 => "TLA2528 ADC",
+            Chip::Tca6408a => "TCA6408A attenuator bus expander",
+            Chip::Cat5171 => "CAT5171 laser digipot",
+        }
+    }
+}
+
+/// A failed bus transaction, with the chip it was addressed to and what it was doing
+#[derive(Debug)]
+pub struct BusError<E> {
+    /// The chip the transaction was for
+    pub chip: Chip,
+    /// 7-bit I2C address the transaction was sent to
+    pub addr: u8,
+    /// What the driver was doing
+    pub op: &'static str,
+    /// The underlying bus error
+    pub source: E,
+}
+
+impl<E> BusError<E> {
+    /// Attach context to a raw bus error, for use with `map_err`
+    pub(crate) fn map(chip: Chip, addr: u8, op: &'static str) -> impl FnOnce(E) -> Self {
+        move |source| Self {
+            chip,
+            addr,
+            op,
+            source,
+        }
+    }
+}
+
+impl<E> BusError<E>
+where
+    E: embedded_hal::i2c::Error,
+{
+    /// The generic kind of the underlying bus error
+    pub fn kind(&self) -> ErrorKind {
+        self.source.kind()
+    }
+}
+
+impl<E> fmt::Display for BusError<E>
+where
+    E: embedded_hal::i2c::Error,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (0x{:02X}): {} failed: {}",
+            self.chip.name(),
+            self.addr,
+            self.op,
+            self.kind()
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for BusError<E> where E: embedded_hal::i2c::Error {}
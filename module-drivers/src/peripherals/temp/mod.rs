@@ -1,22 +1,43 @@
 //! Minimal driver and high-level wrapper for the TMP117 temperature
 //! sensor and ID
 //!
-//! Use all the defaults for now, if we want to muck with averaging and conversions,
-//! we'll want to manage internal state and be a bit more careful
+//! By default the sensor free-runs with full averaging, see [`TempConfig`] to trade
+//! that off for lower power or faster response.
 
 mod regs;
 
-use embedded_hal::i2c::{I2c, Operation};
+use super::{BusError, Chip};
+use core::fmt;
+use embedded_hal::{
+    delay::DelayNs,
+    i2c::{I2c, Operation},
+};
 use packed_struct::PackedStruct;
 use regs::{
-    Addr, AveragingMode, Configuration, ConversionMode, Temperature, EEPROM1, EEPROM2, EEPROM3,
+    Addr, Configuration, DeviceId, EepromUnlock, THighLimit, TLowLimit, TempOffset, Temperature,
+    EEPROM1, EEPROM2, EEPROM3,
 };
+use serde::{Deserialize, Serialize};
+
+pub use regs::{AveragingMode, ConversionCycle, ConversionMode};
+
+/// Temperature (in C) of one LSB, the resolution of every temperature register
+pub const SCALE_C: f32 = 7.8125e-3;
 
-const SCALE_C: f32 = 7.8125e-3;
+/// Value of the DEVICE_ID field for a TMP117
+const TMP117_ID: u16 = 0x117;
+
+/// Time (in ms) between polls of the EEPROM busy flag
+const EEPROM_POLL_MS: u32 = 1;
+
+/// How long (in ms) to wait for an EEPROM programming cycle (7 ms typical) before giving up
+const EEPROM_TIMEOUT_MS: u32 = 50;
 
 struct Tmp117<I2C> {
     bus: I2C,
     addr: u8,
+    /// Alert flags latched by the hardware, kept here as any configuration read clears them
+    latched: AlertFlags,
 }
 
 impl<I2C, E> Tmp117<I2C>
@@ -25,83 +46,237 @@ where
     E: embedded_hal::i2c::Error,
 {
     fn new(bus: I2C, addr: u8) -> Self {
-        Self { bus, addr }
+        Self {
+            bus,
+            addr,
+            latched: AlertFlags::default(),
+        }
     }
 
-    fn read_reg<R, const N: usize>(&mut self) -> Result<R, E>
+    fn read_reg<R, const N: usize>(&mut self) -> Result<R, BusError<E>>
     where
         R: Addr + PackedStruct<ByteArray = [u8; N]>,
     {
         let mut raw = [0u8; N];
-        self.bus.write_read(self.addr, &[R::ADDR], &mut raw)?;
+        self.bus
+            .write_read(self.addr, &[R::ADDR], &mut raw)
+            .map_err(BusError::map(Chip::Tmp117, self.addr, R::READ))?;
         let unpacked = R::unpack(&raw).unwrap();
         Ok(unpacked)
     }
 
-    fn write_reg<R, const N: usize>(&mut self, reg: R) -> Result<(), E>
+    fn write_reg<R, const N: usize>(&mut self, reg: R) -> Result<(), BusError<E>>
     where
         R: Addr + PackedStruct<ByteArray = [u8; N]>,
     {
         let bytes = reg.pack().unwrap();
         let mut operations = [Operation::Write(&[R::ADDR]), Operation::Write(&bytes)];
-        self.bus.transaction(self.addr, &mut operations)?;
+        self.bus
+            .transaction(self.addr, &mut operations)
+            .map_err(BusError::map(Chip::Tmp117, self.addr, R::WRITE))?;
         Ok(())
     }
 
-    fn reset(&mut self) -> Result<(), E> {
+    /// Read the configuration register, holding on to the alert flags it clears
+    fn read_config(&mut self) -> Result<Configuration, BusError<E>> {
+        let conf: Configuration = self.read_reg()?;
+        // In therm mode the flags aren't latched, so reading doesn't lose anything
+        if !conf.t_na {
+            self.latched.high |= conf.high_alert;
+            self.latched.low |= conf.low_alert;
+        }
+        Ok(conf)
+    }
+
+    fn reset(&mut self) -> Result<(), BusError<E>> {
         let con = Configuration {
             soft_reset: true,
             ..Default::default()
         };
         self.write_reg(con)?;
+        self.latched = AlertFlags::default();
         Ok(())
     }
 
-    fn set_cc(&mut self, cc: u8) -> Result<(), E> {
-        let mut conf: Configuration = self.read_reg()?;
+    fn set_conversion(
+        &mut self,
+        mode: ConversionMode,
+        cc: ConversionCycle,
+        avg: AveragingMode,
+    ) -> Result<(), BusError<E>> {
+        let mut conf = self.read_config()?;
+        conf.mode = mode;
         conf.conv = cc;
+        conf.avg = avg;
         self.write_reg(conf)?;
         Ok(())
     }
 
-    fn set_avg(&mut self, avg: AveragingMode) -> Result<(), E> {
-        let mut conf: Configuration = self.read_reg()?;
-        conf.avg = avg;
-        self.write_reg(conf)?;
+    fn set_eeprom_unlock(&mut self, unlock: bool) -> Result<(), BusError<E>> {
+        let reg = EepromUnlock {
+            unlock,
+            ..Default::default()
+        };
+        self.write_reg(reg)?;
         Ok(())
     }
 
-    fn set_mod(&mut self, mode: ConversionMode) -> Result<(), E> {
-        let mut conf: Configuration = self.read_reg()?;
-        conf.mode = mode;
+    fn set_alert_mode(
+        &mut self,
+        therm: bool,
+        pol: bool,
+        dr_alert: bool,
+    ) -> Result<(), BusError<E>> {
+        let mut conf = self.read_config()?;
+        conf.t_na = therm;
+        conf.pol = pol;
+        conf.dr_alert = dr_alert;
         self.write_reg(conf)?;
         Ok(())
     }
 }
 
+/// Conversion settings of the temperature sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TempConfig {
+    /// Continuous, shutdown or one-shot
+    pub mode: ConversionMode,
+    /// Time between results in continuous mode
+    pub cycle: ConversionCycle,
+    /// Number of conversions averaged per result
+    pub avg: AveragingMode,
+}
+
+impl Default for TempConfig {
+    /// Free-run with full averaging (64) and minimum cycle time (so 1s between new samples)
+    fn default() -> Self {
+        Self {
+            mode: ConversionMode::Continuous,
+            cycle: ConversionCycle::_15_5ms,
+            avg: AveragingMode::_64,
+        }
+    }
+}
+
+impl TempConfig {
+    /// Time (in ms) between new results in continuous mode
+    pub fn cycle_time_ms(&self) -> f32 {
+        self.cycle.time_ms().max(self.avg.active_time_ms())
+    }
+}
+
+/// How the limit flags and the ALERT pin respond to the temperature limits
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlertMode {
+    /// Flags latch when a limit is crossed and clear when the configuration register is read
+    #[default]
+    Alert,
+    /// The high flag sets above the high limit and clears below the low limit (hysteresis)
+    Therm,
+}
+
+/// Alert configuration of the temperature sensor
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlertConfig {
+    /// Alert or therm behaviour
+    pub mode: AlertMode,
+    /// Drive the ALERT pin active high (active low otherwise)
+    pub active_high: bool,
+    /// Use the ALERT pin for data ready instead of the limit flags
+    pub data_ready_pin: bool,
+}
+
+/// State of the limit flags
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AlertFlags {
+    /// The high limit was crossed
+    pub high: bool,
+    /// The low limit was crossed
+    pub low: bool,
+}
+
 /// High-level temperature sensor struct
 pub struct TemperataureSensor<I2C>(Tmp117<I2C>);
 
 #[derive(Debug)]
 pub enum Error<E> {
     /// Lower level bus error
-    I2c(E),
+    I2c(BusError<E>),
     /// Timeout while waiting for a conversion
     Timeout,
+    /// Requested temperature can't be represented or the limits are inverted
+    OutOfRange,
+    /// EEPROM read back didn't match what was programmed
+    Verify,
+    /// The device at this address isn't a TMP117 (contains the DEVICE_ID we read instead)
+    UnknownDevice(u16),
+}
+
+/// The general-purpose EEPROM words
+///
+/// Note these come from the factory holding the unique ID reported by `uid`, so
+/// overwriting them changes the identity of the module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EepromWord {
+    /// EEPROM1 (0x05)
+    One,
+    /// EEPROM2 (0x06)
+    Two,
+    /// EEPROM3 (0x08)
+    Three,
 }
 
 // Convert I2C errors into our higher-level error
-impl<E> core::convert::From<E> for Error<E>
+impl<E> core::convert::From<BusError<E>> for Error<E>
 where
     E: embedded_hal::i2c::Error,
 {
-    fn from(value: E) -> Self {
+    fn from(value: BusError<E>) -> Self {
         Error::I2c(value)
     }
 }
 
+impl<E> fmt::Display for Error<E>
+where
+    E: embedded_hal::i2c::Error,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::I2c(e) => e.fmt(f),
+            Error::Timeout => write!(f, "timed out waiting for the temperature sensor"),
+            Error::OutOfRange => write!(f, "temperature out of range (or limits inverted)"),
+            Error::Verify => write!(f, "temperature sensor EEPROM read back didn't match"),
+            Error::UnknownDevice(id) => {
+                write!(f, "expected a TMP117 but read DEVICE_ID 0x{id:04X}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for Error<E>
+where
+    E: embedded_hal::i2c::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::I2c(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 pub type TempResult<T, E> = Result<T, Error<E>>;
 
+/// Convert a temperature in C to the register representation
+fn to_raw<E>(temp: f32) -> TempResult<i16, E> {
+    let raw = temp / SCALE_C;
+    if !(i16::MIN as f32..=i16::MAX as f32).contains(&raw) {
+        return Err(Error::OutOfRange);
+    }
+    Ok(raw as i16)
+}
+
 impl<I2C, E> TemperataureSensor<I2C>
 where
     I2C: I2c<Error = E>,
@@ -113,15 +288,61 @@ where
 
     /// Initialize the temperature sensor
     pub fn init(&mut self) -> TempResult<(), E> {
-        self.0.reset()?;
         // Polling is broken due to a silicon bug, so we'll just have the sensor free-run reading temperatures
-        self.0.set_mod(ConversionMode::Continuous)?;
-        // Might as well go full averaging (64) and minimum cycle time (so 1s between new samples)
-        self.0.set_cc(0)?;
-        self.0.set_avg(AveragingMode::_64)?;
+        self.init_with(TempConfig::default())
+    }
+
+    /// Initialize the temperature sensor with non-default conversion settings
+    pub fn init_with(&mut self, config: TempConfig) -> TempResult<(), E> {
+        let id = self.device_id()?;
+        if id != TMP117_ID {
+            return Err(Error::UnknownDevice(id));
+        }
+        self.0.reset()?;
+        self.configure(config)
+    }
+
+    /// Check (without writing anything) that this is a TMP117 that is converting continuously
+    ///
+    /// Any averaging and conversion cycle is accepted. This reads the configuration register,
+    /// which clears the alert flags latched in hardware (they are kept for `alert_flags`).
+    pub fn is_running(&mut self) -> TempResult<bool, E> {
+        if self.device_id()? != TMP117_ID {
+            return Ok(false);
+        }
+        Ok(self.config()?.mode == ConversionMode::Continuous)
+    }
+
+    /// Change the conversion settings
+    ///
+    /// Setting the mode to [`ConversionMode::OneShot`] triggers a single conversion, after
+    /// which the sensor returns to shutdown on its own. The configuration register is read and
+    /// written back, clearing its latched alert flags (kept for `alert_flags`).
+    pub fn configure(&mut self, config: TempConfig) -> TempResult<(), E> {
+        self.0
+            .set_conversion(config.mode, config.cycle, config.avg)?;
         Ok(())
     }
 
+    /// Read back the current conversion settings
+    ///
+    /// Reading the configuration register clears the latched alert flags in hardware, so they
+    /// are kept for the next `alert_flags`.
+    pub fn config(&mut self) -> TempResult<TempConfig, E> {
+        let conf = self.0.read_config()?;
+        Ok(TempConfig {
+            mode: conf.mode,
+            cycle: conf.conv,
+            avg: conf.avg,
+        })
+    }
+
+    /// Get the device ID (0x117 for a TMP117)
+    pub fn device_id(&mut self) -> TempResult<u16, E> {
+        let reg: DeviceId = self.0.read_reg()?;
+        Ok(reg.did)
+    }
+
     /// Get the unique ID
     pub fn uid(&mut self) -> TempResult<u64, E> {
         let uid1: EEPROM1 = self.0.read_reg()?;
@@ -142,4 +363,192 @@ where
         let raw: Temperature = self.0.read_reg()?;
         Ok(SCALE_C * raw.temp as f32)
     }
+
+    /// Get a temperature in C, making sure the sensor is still converting
+    ///
+    /// This clears the data ready flag and then polls it for a new conversion, for up to three
+    /// conversion cycles as the silicon bug (see `temp`) can occasionally lose the flag for one
+    /// of them. If no conversion completes in that time, or the sensor is shut down, this
+    /// returns [`Error::Timeout`] whatever the conversion mode.
+    ///
+    /// Note this reads the configuration register, which clears the latched alert flags in
+    /// hardware (they are kept for `alert_flags`).
+    pub fn temp_fresh<D: DelayNs>(&mut self, delay: &mut D) -> TempResult<f32, E> {
+        let config = self.config()?;
+        if config.mode == ConversionMode::Shutdown {
+            // Nothing is ever going to update the result
+            return Err(Error::Timeout);
+        }
+        let cycle = config.cycle_time_ms() as u32;
+        let poll = (cycle / 4).max(1);
+        let window = 3 * cycle;
+
+        let mut waited = 0;
+        while waited < window {
+            delay.delay_ms(poll);
+            waited += poll;
+            if self.0.read_config()?.data_ready {
+                let raw: Temperature = self.0.read_reg()?;
+                return Ok(SCALE_C * raw.temp as f32);
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Set the low and high temperature limits in C
+    pub fn set_limits(&mut self, low: f32, high: f32) -> TempResult<(), E> {
+        if low > high {
+            return Err(Error::OutOfRange);
+        }
+        let low = TLowLimit { temp: to_raw(low)? };
+        let high = THighLimit {
+            temp: to_raw(high)?,
+        };
+        self.0.write_reg(low)?;
+        self.0.write_reg(high)?;
+        Ok(())
+    }
+
+    /// Get the low and high temperature limits in C
+    pub fn limits(&mut self) -> TempResult<(f32, f32), E> {
+        let low: TLowLimit = self.0.read_reg()?;
+        let high: THighLimit = self.0.read_reg()?;
+        Ok((SCALE_C * low.temp as f32, SCALE_C * high.temp as f32))
+    }
+
+    /// Change the alert configuration
+    ///
+    /// A read-modify-write of the configuration register, so it clears the latched alert flags
+    /// in hardware (they are kept for `alert_flags`).
+    pub fn set_alert_config(&mut self, config: AlertConfig) -> TempResult<(), E> {
+        self.0.set_alert_mode(
+            config.mode == AlertMode::Therm,
+            config.active_high,
+            config.data_ready_pin,
+        )?;
+        Ok(())
+    }
+
+    /// Read back the alert configuration
+    ///
+    /// Like every read of the configuration register this clears the latched alert flags in
+    /// hardware, they are kept for `alert_flags`.
+    pub fn alert_config(&mut self) -> TempResult<AlertConfig, E> {
+        let conf = self.0.read_config()?;
+        Ok(AlertConfig {
+            mode: if conf.t_na {
+                AlertMode::Therm
+            } else {
+                AlertMode::Alert
+            },
+            active_high: conf.pol,
+            data_ready_pin: conf.dr_alert,
+        })
+    }
+
+    /// Poll the limit flags
+    ///
+    /// In [`AlertMode::Alert`] the flags latch in hardware and any read of the configuration
+    /// register clears them, so the driver holds on to them from every such read. This reports
+    /// any limit crossing since the last call (and clears them). In [`AlertMode::Therm`] this is
+    /// the present state of the flags.
+    pub fn alert_flags(&mut self) -> TempResult<AlertFlags, E> {
+        let conf = self.0.read_config()?;
+        if conf.t_na {
+            return Ok(AlertFlags {
+                high: conf.high_alert,
+                low: conf.low_alert,
+            });
+        }
+        Ok(core::mem::take(&mut self.0.latched))
+    }
+
+    /// Set the offset (in C) the sensor adds to every conversion
+    ///
+    /// This only lasts until the next reset (including `init`), use `persist_offset` to keep it.
+    pub fn set_offset(&mut self, offset: f32) -> TempResult<(), E> {
+        let reg = TempOffset {
+            temp: to_raw(offset)?,
+        };
+        self.0.write_reg(reg)?;
+        Ok(())
+    }
+
+    /// Get the offset (in C) the sensor adds to every conversion
+    pub fn offset(&mut self) -> TempResult<f32, E> {
+        let reg: TempOffset = self.0.read_reg()?;
+        Ok(SCALE_C * reg.temp as f32)
+    }
+
+    /// Program the current offset into EEPROM so it survives resets and power cycles
+    ///
+    /// Waiting for the programming cycle polls the configuration register, clearing the latched
+    /// alert flags in hardware (they are kept for `alert_flags`).
+    pub fn persist_offset<D: DelayNs>(&mut self, delay: &mut D) -> TempResult<(), E> {
+        let reg: TempOffset = self.0.read_reg()?;
+        self.program(reg, delay)
+    }
+
+    /// Program one of the general-purpose EEPROM words
+    ///
+    /// Like `persist_offset`, this clears the latched alert flags in hardware while it waits.
+    pub fn write_eeprom<D: DelayNs>(
+        &mut self,
+        word: EepromWord,
+        data: u16,
+        delay: &mut D,
+    ) -> TempResult<(), E> {
+        match word {
+            EepromWord::One => self.program(EEPROM1 { data }, delay),
+            EepromWord::Two => self.program(EEPROM2 { data }, delay),
+            EepromWord::Three => self.program(EEPROM3 { data }, delay),
+        }
+    }
+
+    /// Read one of the general-purpose EEPROM words
+    pub fn read_eeprom(&mut self, word: EepromWord) -> TempResult<u16, E> {
+        Ok(match word {
+            EepromWord::One => self.0.read_reg::<EEPROM1, 2>()?.data,
+            EepromWord::Two => self.0.read_reg::<EEPROM2, 2>()?.data,
+            EepromWord::Three => self.0.read_reg::<EEPROM3, 2>()?.data,
+        })
+    }
+
+    /// Write an EEPROM-backed register, wait for the programming cycle and verify it
+    fn program<R, const N: usize, D: DelayNs>(&mut self, reg: R, delay: &mut D) -> TempResult<(), E>
+    where
+        R: Addr + PackedStruct<ByteArray = [u8; N]> + PartialEq + Copy,
+    {
+        // While the EEPROM is unlocked, register writes also program the EEPROM
+        self.0.set_eeprom_unlock(true)?;
+        let res = self.0.write_reg(reg).map_err(Error::I2c).and_then(|_| {
+            self.wait_eeprom(delay)?;
+            let readback: R = self.0.read_reg()?;
+            if readback == reg {
+                Ok(())
+            } else {
+                Err(Error::Verify)
+            }
+        });
+        // Always try to lock it again, but report the first thing that went wrong
+        let lock = self.0.set_eeprom_unlock(false);
+        res?;
+        Ok(lock?)
+    }
+
+    /// Poll the EEPROM busy flag until the programming cycle is done
+    fn wait_eeprom<D: DelayNs>(&mut self, delay: &mut D) -> TempResult<(), E> {
+        let mut waited = 0;
+        loop {
+            delay.delay_ms(EEPROM_POLL_MS);
+            waited += EEPROM_POLL_MS;
+            let conf = self.0.read_config()?;
+            if !conf.eeprom_busy {
+                return Ok(());
+            }
+            if waited >= EEPROM_TIMEOUT_MS {
+                return Err(Error::Timeout);
+            }
+        }
+    }
 }
@@ -1,9 +1,24 @@
 //! Incomplete register map for the TMP117 for our use
 
 use packed_struct::prelude::*;
+use serde::{Deserialize, Serialize};
 
 pub(super) trait Addr {
     const ADDR: u8;
+    /// Description of a read of this register, for error reporting
+    const READ: &'static str;
+    /// Description of a write of this register, for error reporting
+    const WRITE: &'static str;
+}
+
+macro_rules! addr {
+    ($reg:ty, $addr:literal, $name:literal) => {
+        impl Addr for $reg {
+            const ADDR: u8 = $addr;
+            const READ: &'static str = concat!("read ", $name);
+            const WRITE: &'static str = concat!("write ", $name);
+        }
+    };
 }
 
 #[derive(PackedStruct, Debug, Default, PartialEq, Eq, Clone, Copy)]
@@ -13,20 +28,23 @@ pub(super) struct Temperature {
     pub(super) temp: i16,
 }
 
-impl Addr for Temperature {
-    const ADDR: u8 = 0x00;
-}
+addr!(Temperature, 0x00, "TEMP_RESULT");
 
-#[derive(PrimitiveEnum_u8, Debug, Default, Clone, Copy, PartialEq, Eq)]
-pub(super) enum ConversionMode {
+/// Conversion mode (MOD)
+#[derive(PrimitiveEnum_u8, Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConversionMode {
+    /// Free-run, updating the result register every conversion cycle
     #[default]
     Continuous = 0b00,
+    /// Stop converting and idle at minimum power
     Shutdown = 0b01,
+    /// Do a single conversion and then go back to shutdown
     OneShot = 0b11,
 }
 
-#[derive(PrimitiveEnum_u8, Debug, Default, Clone, Copy, PartialEq, Eq)]
-pub(super) enum AveragingMode {
+/// Number of conversions averaged for each result (AVG)
+#[derive(PrimitiveEnum_u8, Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AveragingMode {
     None = 0b00,
     #[default]
     _8 = 0b01,
@@ -34,6 +52,51 @@ pub(super) enum AveragingMode {
     _64 = 0b11,
 }
 
+impl AveragingMode {
+    /// Time (in ms) the active conversions for one result take
+    pub fn active_time_ms(&self) -> f32 {
+        match self {
+            AveragingMode::None => 15.5,
+            AveragingMode::_8 => 125.0,
+            AveragingMode::_32 => 500.0,
+            AveragingMode::_64 => 1000.0,
+        }
+    }
+}
+
+/// Conversion cycle time in continuous mode (CONV)
+///
+/// Named by the cycle time without averaging, the real cycle time is never shorter
+/// than the time the averaged conversions take.
+#[derive(PrimitiveEnum_u8, Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConversionCycle {
+    _15_5ms = 0b000,
+    _125ms = 0b001,
+    _250ms = 0b010,
+    _500ms = 0b011,
+    #[default]
+    _1s = 0b100,
+    _4s = 0b101,
+    _8s = 0b110,
+    _16s = 0b111,
+}
+
+impl ConversionCycle {
+    /// Nominal cycle time (in ms) without averaging
+    pub fn time_ms(&self) -> f32 {
+        match self {
+            ConversionCycle::_15_5ms => 15.5,
+            ConversionCycle::_125ms => 125.0,
+            ConversionCycle::_250ms => 250.0,
+            ConversionCycle::_500ms => 500.0,
+            ConversionCycle::_1s => 1000.0,
+            ConversionCycle::_4s => 4000.0,
+            ConversionCycle::_8s => 8000.0,
+            ConversionCycle::_16s => 16000.0,
+        }
+    }
+}
+
 #[derive(PackedStruct, Debug, Default, PartialEq, Eq, Clone, Copy)]
 #[packed_struct(bit_numbering = "lsb0", size_bytes = "2")]
 pub struct Configuration {
@@ -47,8 +110,8 @@ pub struct Configuration {
     pub(super) eeprom_busy: bool,
     #[packed_field(bits = "11..=10", ty = "enum")]
     pub(super) mode: ConversionMode,
-    #[packed_field(bits = "9..=7")]
-    pub(super) conv: u8,
+    #[packed_field(bits = "9..=7", ty = "enum")]
+    pub(super) conv: ConversionCycle,
     #[packed_field(bits = "6..=5", ty = "enum")]
     pub(super) avg: AveragingMode,
     #[packed_field(bits = "4")]
@@ -63,10 +126,39 @@ pub struct Configuration {
     pub(super) _res: ReservedZero<packed_bits::Bits<1>>,
 }
 
-impl Addr for Configuration {
-    const ADDR: u8 = 0x01;
+addr!(Configuration, 0x01, "CONFIGURATION");
+
+#[derive(PackedStruct, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[packed_struct(bit_numbering = "lsb0", size_bytes = "2")]
+pub(super) struct THighLimit {
+    #[packed_field(bits = "15..=0", endian = "msb")]
+    pub(super) temp: i16,
 }
 
+addr!(THighLimit, 0x02, "THIGH_LIMIT");
+
+#[derive(PackedStruct, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[packed_struct(bit_numbering = "lsb0", size_bytes = "2")]
+pub(super) struct TLowLimit {
+    #[packed_field(bits = "15..=0", endian = "msb")]
+    pub(super) temp: i16,
+}
+
+addr!(TLowLimit, 0x03, "TLOW_LIMIT");
+
+#[derive(PackedStruct, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[packed_struct(bit_numbering = "lsb0", size_bytes = "2")]
+pub(super) struct EepromUnlock {
+    #[packed_field(bits = "15")]
+    pub(super) unlock: bool,
+    #[packed_field(bits = "14")]
+    pub(super) busy: bool,
+    #[packed_field(bits = "13..=0")]
+    pub(super) _res: ReservedZero<packed_bits::Bits<14>>,
+}
+
+addr!(EepromUnlock, 0x04, "EEPROM_UL");
+
 #[derive(PackedStruct, Debug, Default, PartialEq, Eq, Clone, Copy)]
 #[packed_struct(bit_numbering = "lsb0", size_bytes = "2")]
 pub(super) struct EEPROM1 {
@@ -74,9 +166,7 @@ pub(super) struct EEPROM1 {
     pub(super) data: u16,
 }
 
-impl Addr for EEPROM1 {
-    const ADDR: u8 = 0x05;
-}
+addr!(EEPROM1, 0x05, "EEPROM1");
 
 #[derive(PackedStruct, Debug, Default, PartialEq, Eq, Clone, Copy)]
 #[packed_struct(bit_numbering = "lsb0", size_bytes = "2")]
@@ -85,10 +175,17 @@ pub(super) struct EEPROM2 {
     pub(super) data: u16,
 }
 
-impl Addr for EEPROM2 {
-    const ADDR: u8 = 0x06;
+addr!(EEPROM2, 0x06, "EEPROM2");
+
+#[derive(PackedStruct, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[packed_struct(bit_numbering = "lsb0", size_bytes = "2")]
+pub(super) struct TempOffset {
+    #[packed_field(bits = "15..=0", endian = "msb")]
+    pub(super) temp: i16,
 }
 
+addr!(TempOffset, 0x07, "TEMP_OFFSET");
+
 #[derive(PackedStruct, Debug, Default, PartialEq, Eq, Clone, Copy)]
 #[packed_struct(bit_numbering = "lsb0", size_bytes = "2")]
 pub(super) struct EEPROM3 {
@@ -96,6 +193,15 @@ pub(super) struct EEPROM3 {
     pub(super) data: u16,
 }
 
-impl Addr for EEPROM3 {
-    const ADDR: u8 = 0x08;
+addr!(EEPROM3, 0x08, "EEPROM3");
+
+#[derive(PackedStruct, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[packed_struct(bit_numbering = "lsb0", size_bytes = "2")]
+pub(super) struct DeviceId {
+    #[packed_field(bits = "15..=12")]
+    pub(super) rev: u8,
+    #[packed_field(bits = "11..=0", endian = "msb")]
+    pub(super) did: u16,
 }
+
+addr!(DeviceId, 0x0F, "DEVICE_ID");
@@ -1,96 +1,63 @@
 //! Python interface for FT4232H boards
 
 use crate::{
-    modules::frx::Frx as InnerFrx, modules::ftx::Ftx as InnerFtx, peripherals::atten::Attenuation,
+    bus::RetryI2c,
+    modules::frx::Frx as InnerFrx,
+    modules::ftx::Ftx as InnerFtx,
+    modules::optical::OpticalCalibration,
+    modules::power::PowerConfig,
+    modules::probe::{probe, ModuleKind},
+    modules::registry::Registry,
+    modules::slope::{append_history, SweepConfig},
+    modules::state::StateFile,
+    modules::RfofModule,
+    peripherals::atten::Attenuation,
 };
-use embedded_hal::i2c::{ErrorType, I2c as I2cTrait};
+use embedded_hal::i2c::{Error as _, ErrorType, I2c as I2cTrait};
 use ftdi_embedded_hal::{
     self as hal,
     libftd2xx::{self, Ft4232ha, Ftdi},
     I2c,
 };
 use pyo3::{
-    exceptions::{PyRuntimeError, PyValueError},
+    exceptions::{PyRuntimeError, PyUserWarning, PyValueError},
     prelude::*,
 };
-use std::sync::{Arc, Mutex};
-
-// ------ Adding support for native Rpi i2c using linux-embedded-hal -------
-
-use linux_embedded_hal::{I2cdev};
-
-type SharedLinuxI2C = Arc<Mutex<I2cdev>>;
-
-struct SharedLinuxI2CDevice(SharedLinuxI2C);
-
-impl SharedLinuxI2CDevice {
-    fn new(bus: &SharedLinuxI2C) -> Self {
-        Self(Arc::clone(bus))
-    }
-}
-
-impl embedded_hal::i2c::ErrorType for SharedLinuxI2CDevice {
-    type Error = <I2cdev as embedded_hal::i2c::ErrorType>::Error;
-}
-
-impl I2cTrait for SharedLinuxI2CDevice {
-    fn read(&mut self, address: u8, bytes: &mut [u8]) -> Result<(), Self::Error> {
-        let mut guard = self.0.lock().unwrap();
-        guard.read(address, bytes)
-    }
+use std::{
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
-        let mut guard = self.0.lock().unwrap();
-        guard.write(address, bytes)
-    }
+use linux_embedded_hal::{Delay, I2cdev};
 
-    fn write_read(
-        &mut self,
-        address: u8,
-        write: &[u8],
-        read: &mut [u8],
-    ) -> Result<(), Self::Error> {
-        let mut guard = self.0.lock().unwrap();
-        guard.write_read(address, write, read)
-    }
+/// A bus shared between every chip on a module, cloning it hands out another handle
+struct Shared<T>(Arc<Mutex<T>>);
 
-    fn transaction(
-        &mut self,
-        address: u8,
-        operations: &mut [embedded_hal::i2c::Operation<'_>],
-    ) -> Result<(), Self::Error> {
-        let mut guard = self.0.lock().unwrap();
-        guard.transaction(address, operations)
+impl<T> Shared<T> {
+    fn new(bus: T) -> Self {
+        Self(Arc::new(Mutex::new(bus)))
     }
 }
 
-// ---------------------- end, 03/14/2025 --------------------
-
-type SharedFTDIDevice = Arc<Mutex<I2c<Ft4232ha>>>;
-
-struct SharedDeivce(SharedFTDIDevice);
-
-impl SharedDeivce {
-    fn new(bus: &SharedFTDIDevice) -> Self {
-        Self(Arc::clone(bus))
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
     }
 }
 
-impl ErrorType for SharedDeivce {
-    type Error = <I2c<Ft4232ha> as embedded_hal::i2c::ErrorType>::Error;
+impl<T: ErrorType> ErrorType for Shared<T> {
+    type Error = T::Error;
 }
 
-impl I2cTrait for SharedDeivce {
+impl<T: I2cTrait> I2cTrait for Shared<T> {
     #[inline]
     fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
-        let bus = &mut *self.0.lock().unwrap();
-        bus.read(address, read)
+        self.0.lock().unwrap().read(address, read)
     }
 
     #[inline]
     fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
-        let bus = &mut *self.0.lock().unwrap();
-        bus.write(address, write)
+        self.0.lock().unwrap().write(address, write)
     }
 
     #[inline]
@@ -100,8 +67,7 @@ impl I2cTrait for SharedDeivce {
         write: &[u8],
         read: &mut [u8],
     ) -> Result<(), Self::Error> {
-        let bus = &mut *self.0.lock().unwrap();
-        bus.write_read(address, write, read)
+        self.0.lock().unwrap().write_read(address, write, read)
     }
 
     #[inline]
@@ -110,11 +76,30 @@ impl I2cTrait for SharedDeivce {
         address: u8,
         operations: &mut [embedded_hal::i2c::Operation<'_>],
     ) -> Result<(), Self::Error> {
-        let bus = &mut *self.0.lock().unwrap();
-        bus.transaction(address, operations)
+        self.0.lock().unwrap().transaction(address, operations)
     }
 }
 
+/// FT4232HA I2C interface, retrying the odd failed transaction
+type FtdiBus = Shared<RetryI2c<I2c<Ft4232ha>, Delay>>;
+
+/// Linux I2C bus (e.g. Rpi), retrying the odd failed transaction
+type LinuxBus = Shared<RetryI2c<I2cdev, Delay>>;
+
+/// Surface a driver error to python with its full description
+fn py_err<T: core::fmt::Display>(e: T) -> PyErr {
+    PyRuntimeError::new_err(e.to_string())
+}
+
+/// Make sure the bus holds the expected kind of module before we try to initialize it
+fn check_module<B: I2cTrait>(mut bus: B, kind: ModuleKind) -> PyResult<()> {
+    let report = probe(&mut bus)
+        .map_err(|e| PyRuntimeError::new_err(format!("Error probing the bus: {}", e.kind())))?;
+    report.expect(kind).map_err(|chip| {
+        PyRuntimeError::new_err(format!("{chip} did not respond, is this an {kind}? ({report})"))
+    })
+}
+
 #[pyfunction]
 /// List available FTDI devices
 pub fn list_devices() -> PyResult<Vec<String>> {
@@ -125,499 +110,466 @@ pub fn list_devices() -> PyResult<Vec<String>> {
         .collect())
 }
 
-#[pyclass]
-struct Ftx(InnerFtx<SharedDeivce>);
-
-#[pymethods]
-impl Ftx {
-    #[new]
-    fn new(idx: i32) -> PyResult<Self> {
-        // Open the FTDI device
-        let device: Ft4232ha = Ftdi::with_index(idx)
-            .map_err(|_| PyValueError::new_err("Could not find a device with that index"))?
-            .try_into()
-            .map_err(|_| PyRuntimeError::new_err("Device was not an FT4232HA"))?;
-
-        // Extract the I2C interface
-        let hal = hal::FtHal::init_freq(device, 100_000)
-            .map_err(|_| PyRuntimeError::new_err("Could not start the interface"))?
-            .i2c()
-            .map_err(|_| PyRuntimeError::new_err("Could not instantiate I2C"))?;
-
-        // Construct the bus (move to the heap behind a mutex with reference counting)
-        let bus = Arc::new(Mutex::new(hal));
-
-        // Construct the inner device
-        let mut inner = InnerFtx::new(
-            SharedDeivce::new(&bus),
-            SharedDeivce::new(&bus),
-            SharedDeivce::new(&bus),
-            SharedDeivce::new(&bus),
-        );
-
-        // Initialize
-        inner
-            .init()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))?;
-
-        Ok(Self(inner))
-    }
-
-    /// Get the board temperature in deg C
-    pub fn get_temp(&mut self) -> PyResult<f32> {
-        self.0
-            .temp
-            .temp()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the unique ID as an integer
-    pub fn get_uid(&mut self) -> PyResult<u64> {
-        self.0
-            .temp
-            .uid()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the current attenuator state in dB
-    pub fn get_atten(&mut self) -> PyResult<f32> {
-        let atten = self
-            .0
-            .atten
-            .get()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))? as u8;
-        let atten = atten as f32 * 0.25;
-        Ok(atten)
-    }
+/// Generates the python methods common to every module (through [`RfofModule`]),
+/// alongside the class-specific methods in `$extra`
+macro_rules! module_methods {
+    ($class:ident, { $($extra:tt)* }) => {
+        #[pymethods]
+        impl $class {
+            $($extra)*
+
+            /// Get the board temperature in deg C
+            pub fn get_temp(&mut self) -> PyResult<f32> {
+                self.0
+                    .temp()
+                    .map_err(py_err)
+            }
+
+            /// Get the unique ID as an integer
+            pub fn get_uid(&mut self) -> PyResult<u64> {
+                self.0
+                    .uid()
+                    .map_err(py_err)
+            }
+
+            /// Get the current attenuator state in dB
+            pub fn get_atten(&mut self) -> PyResult<f32> {
+                self.0
+                    .atten()
+                    .map_err(py_err)
+            }
+
+            /// Set the state of the digital step attenuator in dB
+            pub fn set_atten(&mut self, atten: f32) -> PyResult<()> {
+                if Attenuation::from_db(atten).is_none() {
+                    Err(PyValueError::new_err("attenuation out of bounds"))
+                } else {
+                    self.0
+                        .set_atten(atten)
+                        .map_err(py_err)
+                }
+            }
+
+            /// Get the RF current at the power detector in dBm
+            pub fn get_rf_power(&mut self) -> PyResult<f32> {
+                self.0
+                    .rf_power()
+                    .map_err(py_err)
+            }
+
+            /// Set the photodiode responsivity in A/W at a wavelength in nm, for this module
+            pub fn set_responsivity(&mut self, responsivity: f32, wavelength_nm: f32) -> PyResult<()> {
+                let uid = self.0.uid().map_err(py_err)?;
+                self.0
+                    .set_optical_calibration(OpticalCalibration::new(uid, responsivity, wavelength_nm))
+                    .map_err(py_err)
+            }
+
+            /// Get the optical power estimated from the photodiode current in dBm
+            pub fn get_optical_power(&mut self) -> PyResult<f32> {
+                self.0
+                    .optical_power()
+                    .map_err(py_err)
+            }
+
+            /// Look this module up in a registry file (TOML or JSON) and use its calibration,
+            /// warning about an unknown unit or one registered somewhere other than `location`.
+            /// Returns the serial number, if the module is registered
+            #[pyo3(signature = (registry, location=None))]
+            pub fn identify(
+                &mut self,
+                py: Python<'_>,
+                registry: &str,
+                location: Option<&str>,
+            ) -> PyResult<Option<String>> {
+                let registry = Registry::load(registry).map_err(|e| {
+                    PyRuntimeError::new_err(format!("Could not load the registry: {e}"))
+                })?;
+                let identity = self.0.identify(&registry, location).map_err(py_err)?;
+                let category = py.get_type_bound::<PyUserWarning>();
+                for warning in &identity.warnings {
+                    PyErr::warn_bound(py, &category, &warning.to_string(), 1)?;
+                }
+                Ok(identity.serial().map(str::to_string))
+            }
+        }
 
-    /// Set the state of the digital step attenuator in dB
-    pub fn set_atten(&mut self, atten: f32) -> PyResult<()> {
-        if !(0.0..=31.75).contains(&atten) {
-            Err(PyValueError::new_err("attenuation out of bounds"))
-        } else {
-            let word = (atten / 0.25) as u8;
-            let atten = unsafe { core::mem::transmute::<u8, Attenuation>(word & 127) };
-            self.0
-                .atten
-                .set(atten)
-                .map_err(|_| PyRuntimeError::new_err("I2C Error"))?;
-            Ok(())
+        impl $class {
+            /// Identify a freshly opened module, if given a registry
+            fn identified(
+                mut self,
+                py: Python<'_>,
+                registry: Option<&str>,
+                location: Option<&str>,
+            ) -> PyResult<Self> {
+                if let Some(registry) = registry {
+                    self.identify(py, registry, location)?;
+                }
+                Ok(self)
+            }
         }
-    }
+    };
+}
 
-    /// Get the RF current at the power detector in dBm
-    pub fn get_rf_power(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .rf_power()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
+/// The FTX methods, shared by the FTDI and Rpi classes
+macro_rules! ftx_methods {
+    ($class:ident, { $($ctor:tt)* }) => {
+        module_methods!($class, {
+            $($ctor)*
+
+            /// Get the DC monitor photodiode current in uA
+            pub fn get_pd_current(&mut self) -> PyResult<f32> {
+                self.0
+                    .adc
+                    .pd_current()
+                    .map_err(py_err)
+            }
+
+            /// Get the DC laser current in mA
+            pub fn get_ld_current(&mut self) -> PyResult<f32> {
+                self.0
+                    .adc
+                    .ld_current()
+                    .map_err(py_err)
+            }
+
+            /// Get the analog supply (VDDA) voltage in V
+            pub fn get_vdda_voltage(&mut self) -> PyResult<f32> {
+                self.0
+                    .adc
+                    .analog_voltage()
+                    .map_err(py_err)
+            }
+
+            /// Get the digital supply (VDD) voltage in V
+            pub fn get_vdd_voltage(&mut self) -> PyResult<f32> {
+                self.0
+                    .adc
+                    .digital_voltage()
+                    .map_err(py_err)
+            }
+
+            /// Get the LNA voltage in V
+            pub fn get_lna_voltage(&mut self) -> PyResult<f32> {
+                self.0
+                    .adc
+                    .lna_voltage()
+                    .map_err(py_err)
+            }
+
+            /// Get the LNA current in mA
+            pub fn get_lna_current(&mut self) -> PyResult<f32> {
+                self.0
+                    .adc
+                    .lna_current()
+                    .map_err(py_err)
+            }
+
+            /// Control the load switch for the LNA bias
+            pub fn set_lna_enable(&mut self, enable: bool) -> PyResult<()> {
+                self.0.adc.enable_lna(enable).map_err(py_err)
+            }
+
+            /// Set the laser current in mA (0-50)
+            pub fn set_ld_current(&mut self, current: f32) -> PyResult<()> {
+                self.0
+                    .set_ld_current(current)
+                    .map_err(|e| {
+                        PyRuntimeError::new_err(format!("Could not set the laser current: {e}"))
+                    })
+            }
+
+            /// Power up in order (supplies, LNA, laser ramp to `ld_current` mA, photodiode check)
+            pub fn power_up(&mut self, ld_current: f32) -> PyResult<()> {
+                let config = PowerConfig {
+                    ld_current,
+                    ..Default::default()
+                };
+                self.0
+                    .power_up(&config, &mut Delay)
+                    .map_err(|e| PyRuntimeError::new_err(format!("Power-up failed: {e}")))?;
+                Ok(())
+            }
+
+            /// Power down in order (laser ramp to off, then the LNA)
+            pub fn power_down(&mut self) -> PyResult<()> {
+                self.0
+                    .power_down(&PowerConfig::default(), &mut Delay)
+                    .map_err(|e| PyRuntimeError::new_err(format!("Power-down failed: {e}")))
+            }
+
+            /// Sweep the laser and fit its (threshold mA, slope efficiency uA/mA), optionally
+            /// appending the measurement to a JSON-lines history file
+            #[pyo3(signature = (history=None))]
+            pub fn measure_slope(&mut self, history: Option<&str>) -> PyResult<(f32, f32)> {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                let record = self
+                    .0
+                    .measure_slope(&SweepConfig::default(), timestamp, &mut Delay)
+                    .map_err(|e| PyRuntimeError::new_err(format!("Slope sweep failed: {e}")))?;
+                if let Some(path) = history {
+                    append_history(path, &record).map_err(py_err)?;
+                }
+                Ok((record.threshold_ma, record.slope_ua_per_ma))
+            }
+        });
+    };
+}
 
-    /// Get the DC monitor photodiode current in uA
-    pub fn get_pd_current(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .pd_current()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
+/// The FRX methods, shared by the FTDI and Rpi classes
+macro_rules! frx_methods {
+    ($class:ident, { $($ctor:tt)* }) => {
+        module_methods!($class, {
+            $($ctor)*
+
+            /// Get the DC photodiode current in mA
+            pub fn get_pd_current(&mut self) -> PyResult<f32> {
+                self.0
+                    .pd_current()
+                    .map_err(py_err)
+            }
+        });
+    };
+}
 
-    /// Get the DC laser current in mA
-    pub fn get_ld_current(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .ld_current()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
+/// Open an FT4232HA interface as a shared I2C bus
+fn open_ftdi(idx: i32, freq: u32) -> PyResult<FtdiBus> {
+    // Open the FTDI device
+    let device: Ft4232ha = Ftdi::with_index(idx)
+        .map_err(|_| PyValueError::new_err("Could not find a device with that index"))?
+        .try_into()
+        .map_err(|_| PyRuntimeError::new_err("Device was not an FT4232HA"))?;
+
+    // Extract the I2C interface
+    let hal = hal::FtHal::init_freq(device, freq)
+        .map_err(|_| PyRuntimeError::new_err("Could not start the interface"))?
+        .i2c()
+        .map_err(|_| PyRuntimeError::new_err("Could not instantiate I2C"))?;
+
+    // Construct the bus (move to the heap behind a mutex with reference counting),
+    // retrying the odd failed transaction
+    Ok(Shared::new(RetryI2c::new(hal, Delay)))
+}
 
-    /// Get the analog supply (VDDA) voltage in V
-    pub fn get_vdda_voltage(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .analog_voltage()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
+/// Open a linux I2C bus (e.g. /dev/i2c-1) as a shared bus
+fn open_linux(bus_path: &str) -> PyResult<LinuxBus> {
+    let i2cdev =
+        I2cdev::new(bus_path).map_err(|_| PyRuntimeError::new_err("Could not open I2C bus"))?;
+    Ok(Shared::new(RetryI2c::new(i2cdev, Delay)))
+}
 
-    /// Get the digital supply (VDD) voltage in V
-    pub fn get_vdd_voltage(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .digital_voltage()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
+#[pyclass]
+struct Ftx(InnerFtx<FtdiBus>);
 
-    /// Get the LNA voltage in V
-    pub fn get_lna_voltage(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .lna_voltage()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
+impl Ftx {
+    fn open(idx: i32, attach: bool) -> PyResult<Self> {
+        let bus = open_ftdi(idx, 100_000)?;
 
-    /// Get the LNA current in mA
-    pub fn get_lna_current(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .lna_current()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
+        // Make sure it's really an FTX
+        check_module(bus.clone(), ModuleKind::Ftx)?;
 
-    /// Control the load switch for the LNA bias
-    pub fn set_lna_enable(&mut self, enable: bool) -> PyResult<()> {
-        self.0.adc.enable_lna(enable).unwrap();
-        //.map_err(|_| PyRuntimeError::new_err("I2C Error"))?;
-        Ok(())
-    }
+        let inner = if attach {
+            InnerFtx::attach_shared(bus)
+                .map_err(|e| PyRuntimeError::new_err(format!("Could not attach: {e}")))?
+        } else {
+            let mut inner = InnerFtx::new_shared(bus);
+            inner
+                .init()
+                .map_err(py_err)?;
+            inner
+        };
 
-    /// Set the laser current in mA (0-50)
-    pub fn set_ld_current(&mut self, current: f32) -> PyResult<()> {
-        self.0
-            .digipot
-            .set(current)
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))?;
-        Ok(())
+        Ok(Self(inner))
     }
 }
 
+ftx_methods!(Ftx, {
+    #[new]
+    #[pyo3(signature = (idx, registry=None, location=None))]
+    fn new(
+        py: Python<'_>,
+        idx: i32,
+        registry: Option<&str>,
+        location: Option<&str>,
+    ) -> PyResult<Self> {
+        Self::open(idx, false)?.identified(py, registry, location)
+    }
+
+    /// Attach to an FTX that is already running, without resetting it
+    #[staticmethod]
+    #[pyo3(signature = (idx, registry=None, location=None))]
+    fn attach(
+        py: Python<'_>,
+        idx: i32,
+        registry: Option<&str>,
+        location: Option<&str>,
+    ) -> PyResult<Self> {
+        Self::open(idx, true)?.identified(py, registry, location)
+    }
+});
+
 // ----------- Adding Support for Rpi, Ftx class --------
 #[pyclass]
-struct FtxPi(InnerFtx<SharedLinuxI2CDevice>);
+struct FtxPi(InnerFtx<LinuxBus>);
 
-#[pymethods]
 impl FtxPi {
-    // new constuctor for Rpi or other linux i2c.
-    // ex. usage from python: 'FtxPi("/dev/i2c-1")'
-    #[new]
-    fn new(bus_path: &str) -> PyResult<Self> {
-        // 1. opens the linux i2c bus (e.g. /dev/i2c-1)
-        let i2cdev = I2cdev::new(bus_path)
-            .map_err(|_| PyRuntimeError::new_err("Could not open I2C bus"))?;
-        
-        // 2. wrap it in Arc<mutex<...>>
-        let bus = Arc::new(Mutex::new(i2cdev));
-
-        // 3. Construct the existing 'InnerFtx' with 4 copies if needed 
-        let mut inner = InnerFtx::new(
-            SharedLinuxI2CDevice::new(&bus),
-            SharedLinuxI2CDevice::new(&bus),
-            SharedLinuxI2CDevice::new(&bus),
-            SharedLinuxI2CDevice::new(&bus),
-        );
-
-        // 4) Initialize
-        inner
-            .init()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error initializing Ftx"))?;
-
-        Ok(Self(inner))
-    }
-
-    /// The same exact methods as the Ftx class
-
-    /// Get the board temperature in deg C
-    pub fn get_temp(&mut self) -> PyResult<f32> {
-        self.0
-            .temp
-            .temp()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the unique ID as an integer
-    pub fn get_uid(&mut self) -> PyResult<u64> {
-        self.0
-            .temp
-            .uid()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the current attenuator state in dB
-    pub fn get_atten(&mut self) -> PyResult<f32> {
-        let atten = self
-            .0
-            .atten
-            .get()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))? as u8;
-        let atten = atten as f32 * 0.25;
-        Ok(atten)
-    }
-
-    /// Set the state of the digital step attenuator in dB
-    pub fn set_atten(&mut self, atten: f32) -> PyResult<()> {
-        if !(0.0..=31.75).contains(&atten) {
-            Err(PyValueError::new_err("attenuation out of bounds"))
+    fn open(bus_path: &str, attach: bool) -> PyResult<Self> {
+        // 1. opens the linux i2c bus (e.g. /dev/i2c-1), shared between the chips
+        let bus = open_linux(bus_path)?;
+
+        // 2. Make sure it's really an FTX
+        check_module(bus.clone(), ModuleKind::Ftx)?;
+
+        // 3) Construct on the one bus and initialize, or check it already was
+        let inner = if attach {
+            InnerFtx::attach_shared(bus).map_err(|e| {
+                PyRuntimeError::new_err(format!("Could not attach to Ftx: {e}"))
+            })?
         } else {
-            let word = (atten / 0.25) as u8;
-            let atten = unsafe { core::mem::transmute::<u8, Attenuation>(word & 127) };
-            self.0
-                .atten
-                .set(atten)
-                .map_err(|_| PyRuntimeError::new_err("I2C Error"))?;
-            Ok(())
-        }
-    }
+            let mut inner = InnerFtx::new_shared(bus);
+            inner
+                .init()
+                .map_err(|e| PyRuntimeError::new_err(format!("Error initializing Ftx: {e}")))?;
+            inner
+        };
 
-    /// Get the RF current at the power detector in dBm
-    pub fn get_rf_power(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .rf_power()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the DC monitor photodiode current in uA
-    pub fn get_pd_current(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .pd_current()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the DC laser current in mA
-    pub fn get_ld_current(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .ld_current()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the analog supply (VDDA) voltage in V
-    pub fn get_vdda_voltage(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .analog_voltage()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the digital supply (VDD) voltage in V
-    pub fn get_vdd_voltage(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .digital_voltage()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the LNA voltage in V
-    pub fn get_lna_voltage(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .lna_voltage()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the LNA current in mA
-    pub fn get_lna_current(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .lna_current()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Control the load switch for the LNA bias
-    pub fn set_lna_enable(&mut self, enable: bool) -> PyResult<()> {
-        self.0.adc.enable_lna(enable).unwrap();
-        //.map_err(|_| PyRuntimeError::new_err("I2C Error"))?;
-        Ok(())
-    }
-
-    /// Set the laser current in mA (0-50)
-    pub fn set_ld_current(&mut self, current: f32) -> PyResult<()> {
-        self.0
-            .digipot
-            .set(current)
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))?;
-        Ok(())
+        Ok(Self(inner))
     }
 }
 
+ftx_methods!(FtxPi, {
+    // new constuctor for Rpi or other linux i2c.
+    // ex. usage from python: 'FtxPi("/dev/i2c-1")'
+    #[new]
+    #[pyo3(signature = (bus_path, registry=None, location=None))]
+    fn new(
+        py: Python<'_>,
+        bus_path: &str,
+        registry: Option<&str>,
+        location: Option<&str>,
+    ) -> PyResult<Self> {
+        Self::open(bus_path, false)?.identified(py, registry, location)
+    }
+
+    /// Attach to an FTX that is already running, without resetting it
+    #[staticmethod]
+    #[pyo3(signature = (bus_path, registry=None, location=None))]
+    fn attach(
+        py: Python<'_>,
+        bus_path: &str,
+        registry: Option<&str>,
+        location: Option<&str>,
+    ) -> PyResult<Self> {
+        Self::open(bus_path, true)?.identified(py, registry, location)
+    }
+});
 
 // ----------------------------- end, 03/14/2025 -------------------------
 
 #[pyclass]
-struct Frx(InnerFrx<SharedDeivce>);
+struct Frx(InnerFrx<FtdiBus>);
 
-#[pymethods]
 impl Frx {
-    #[new]
-    fn new(idx: i32) -> PyResult<Self> {
-        // Open the FTDI device
-        let device: Ft4232ha = Ftdi::with_index(idx)
-            .map_err(|_| PyValueError::new_err("Could not find a device with that index"))?
-            .try_into()
-            .map_err(|_| PyRuntimeError::new_err("Device was not an FT4232HA"))?;
-
-        // Extract the I2C interface
-        let hal = hal::FtHal::init_freq(device, 10_000)
-            .map_err(|_| PyRuntimeError::new_err("Could not start the interface"))?
-            .i2c()
-            .map_err(|_| PyRuntimeError::new_err("Could not instantiate I2C"))?;
-
-        // Construct the bus (move to the heap behind a mutex with reference counting)
-        let bus = Arc::new(Mutex::new(hal));
-
-        // Construct the inner device
-        let mut inner = InnerFrx::new(
-            SharedDeivce::new(&bus),
-            SharedDeivce::new(&bus),
-            SharedDeivce::new(&bus),
-        );
-
-        // Initialize
-        inner
-            .init()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))?;
-
-        Ok(Self(inner))
-    }
-
-    /// Get the board temperature in deg C
-    pub fn get_temp(&mut self) -> PyResult<f32> {
-        self.0
-            .temp
-            .temp()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the RF current at the power detector in dBm
-    pub fn get_rf_power(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .rf_power()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the DC photodiode current in mA
-    pub fn get_pd_current(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .pd_current()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the unique ID as an integer
-    pub fn get_uid(&mut self) -> PyResult<u64> {
-        self.0
-            .temp
-            .uid()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
+    fn open(idx: i32, attach: bool) -> PyResult<Self> {
+        let bus = open_ftdi(idx, 10_000)?;
 
-    /// Get the current attenuator state in dB
-    pub fn get_atten(&mut self) -> PyResult<f32> {
-        let atten = self
-            .0
-            .atten
-            .get()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))? as u8;
-        let atten = atten as f32 * 0.25;
-        Ok(atten)
-    }
+        // Make sure it's really an FRX
+        check_module(bus.clone(), ModuleKind::Frx)?;
 
-    /// Set the state of the digital step attenuator in dB
-    pub fn set_atten(&mut self, atten: f32) -> PyResult<()> {
-        if !(0.0..=31.75).contains(&atten) {
-            Err(PyValueError::new_err("attenuation out of bounds"))
+        let inner = if attach {
+            InnerFrx::attach_shared(bus)
+                .map_err(|e| PyRuntimeError::new_err(format!("Could not attach: {e}")))?
         } else {
-            let word = (atten / 0.25) as u8;
-            let atten = unsafe { core::mem::transmute::<u8, Attenuation>(word & 127) };
-            self.0
-                .atten
-                .set(atten)
-                .map_err(|_| PyRuntimeError::new_err("I2C Error"))?;
-            Ok(())
-        }
+            let mut inner = InnerFrx::new_shared(bus);
+            inner
+                .init()
+                .map_err(py_err)?;
+            inner
+        };
+
+        Ok(Self(inner))
     }
 }
 
+frx_methods!(Frx, {
+    #[new]
+    #[pyo3(signature = (idx, registry=None, location=None))]
+    fn new(
+        py: Python<'_>,
+        idx: i32,
+        registry: Option<&str>,
+        location: Option<&str>,
+    ) -> PyResult<Self> {
+        Self::open(idx, false)?.identified(py, registry, location)
+    }
+
+    /// Attach to an FRX that is already running, without resetting it
+    #[staticmethod]
+    #[pyo3(signature = (idx, registry=None, location=None))]
+    fn attach(
+        py: Python<'_>,
+        idx: i32,
+        registry: Option<&str>,
+        location: Option<&str>,
+    ) -> PyResult<Self> {
+        Self::open(idx, true)?.identified(py, registry, location)
+    }
+});
+
 // ---------------------- Adding Support for Rpi, FrxPi Class ------------
 #[pyclass]
-struct FrxPi(InnerFrx<SharedLinuxI2CDevice>);
+struct FrxPi(InnerFrx<LinuxBus>);
 
-#[pymethods]
 impl FrxPi {
-    #[new]
-    fn new(bus_path: &str) -> PyResult<Self> {
-        let i2cdev = I2cdev::new(bus_path)
-            .map_err(|_| PyRuntimeError::new_err("Could not open I2C bus"))?;
-
-        let bus = Arc::new(Mutex::new(i2cdev));
-        let mut inner = InnerFrx::new(
-            SharedLinuxI2CDevice::new(&bus),
-            SharedLinuxI2CDevice::new(&bus),
-            SharedLinuxI2CDevice::new(&bus),
-        );
-
-        inner
-            .init()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error initializing Frx"))?;
+    fn open(bus_path: &str, attach: bool) -> PyResult<Self> {
+        let bus = open_linux(bus_path)?;
+        check_module(bus.clone(), ModuleKind::Frx)?;
+
+        let inner = if attach {
+            InnerFrx::attach_shared(bus).map_err(|e| {
+                PyRuntimeError::new_err(format!("Could not attach to Frx: {e}"))
+            })?
+        } else {
+            let mut inner = InnerFrx::new_shared(bus);
+            inner
+                .init()
+                .map_err(|e| PyRuntimeError::new_err(format!("Error initializing Frx: {e}")))?;
+            inner
+        };
 
         Ok(Self(inner))
     }
-
-
-    /// Get the board temperature in deg C
-    pub fn get_temp(&mut self) -> PyResult<f32> {
-        self.0
-            .temp
-            .temp()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the RF current at the power detector in dBm
-    pub fn get_rf_power(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .rf_power()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the DC photodiode current in mA
-    pub fn get_pd_current(&mut self) -> PyResult<f32> {
-        self.0
-            .adc
-            .pd_current()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the unique ID as an integer
-    pub fn get_uid(&mut self) -> PyResult<u64> {
-        self.0
-            .temp
-            .uid()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))
-    }
-
-    /// Get the current attenuator state in dB
-    pub fn get_atten(&mut self) -> PyResult<f32> {
-        let atten = self
-            .0
-            .atten
-            .get()
-            .map_err(|_| PyRuntimeError::new_err("I2C Error"))? as u8;
-        let atten = atten as f32 * 0.25;
-        Ok(atten)
-    }
-
-    /// Set the state of the digital step attenuator in dB
-    pub fn set_atten(&mut self, atten: f32) -> PyResult<()> {
-        if !(0.0..=31.75).contains(&atten) {
-            Err(PyValueError::new_err("attenuation out of bounds"))
-        } else {
-            let word = (atten / 0.25) as u8;
-            let atten = unsafe { core::mem::transmute::<u8, Attenuation>(word & 127) };
-            self.0
-                .atten
-                .set(atten)
-                .map_err(|_| PyRuntimeError::new_err("I2C Error"))?;
-            Ok(())
-        }
-    }
 }
 
-// ---------------------- end, 03/14/2025 ----------------------------
-
-
-
-
+frx_methods!(FrxPi, {
+    #[new]
+    #[pyo3(signature = (bus_path, registry=None, location=None))]
+    fn new(
+        py: Python<'_>,
+        bus_path: &str,
+        registry: Option<&str>,
+        location: Option<&str>,
+    ) -> PyResult<Self> {
+        Self::open(bus_path, false)?.identified(py, registry, location)
+    }
+
+    /// Attach to an FRX that is already running, without resetting it
+    #[staticmethod]
+    #[pyo3(signature = (bus_path, registry=None, location=None))]
+    fn attach(
+        py: Python<'_>,
+        bus_path: &str,
+        registry: Option<&str>,
+        location: Option<&str>,
+    ) -> PyResult<Self> {
+        Self::open(bus_path, true)?.identified(py, registry, location)
+    }
+});
 
+// ---------------------- end, 03/14/2025 ----------------------------
 
 #[pymodule]
 pub fn rfof(module: &Bound<'_, PyModule>) -> PyResult<()> {
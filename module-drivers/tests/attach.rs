@@ -0,0 +1,72 @@
+//! Attaching to modules that are already running
+
+use embedded_hal_bus::i2c::RefCellDevice;
+use rfof::{
+    bus::SimBus,
+    modules::{
+        frx::{self, Frx},
+        ftx::{self, Ftx},
+        probe::Chip,
+    },
+    peripherals::temp::{ConversionMode, TempConfig},
+};
+use std::cell::RefCell;
+
+fn dev(bus: &RefCell<SimBus>) -> RefCellDevice<'_, SimBus> {
+    RefCellDevice::new(bus)
+}
+
+#[test]
+fn attach_needs_an_initialized_module() {
+    let bus = RefCell::new(SimBus::ftx());
+    assert!(matches!(
+        Ftx::attach(dev(&bus), dev(&bus), dev(&bus), dev(&bus)),
+        Err(ftx::Error::NotConfigured(Chip::Tca6408a))
+    ));
+
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.atten.set_db(7.5).unwrap();
+    let writes = bus.borrow().register_writes;
+    let mut attached = Ftx::attach(dev(&bus), dev(&bus), dev(&bus), dev(&bus)).unwrap();
+    assert_eq!(bus.borrow().register_writes, writes);
+    assert_eq!(attached.atten.get_db().unwrap(), 7.5);
+
+    let bus = RefCell::new(SimBus::frx());
+    Frx::new_refcell(&bus).init().unwrap();
+    let writes = bus.borrow().register_writes;
+    Frx::attach(dev(&bus), dev(&bus), dev(&bus)).unwrap();
+    assert_eq!(bus.borrow().register_writes, writes);
+}
+
+#[test]
+fn attach_names_the_chip_that_isnt_running() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.temp
+        .configure(TempConfig {
+            mode: ConversionMode::Shutdown,
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(matches!(
+        Ftx::attach(dev(&bus), dev(&bus), dev(&bus), dev(&bus)),
+        Err(ftx::Error::NotConfigured(Chip::Tmp117))
+    ));
+
+    // The digipot has nothing to configure, but has to be there
+    ftx.init().unwrap();
+    bus.borrow_mut().remove(Chip::Cat5171);
+    assert!(matches!(
+        Ftx::attach(dev(&bus), dev(&bus), dev(&bus), dev(&bus)),
+        Err(ftx::Error::Digipot(_))
+    ));
+
+    // An FRX that was never initialized
+    let bus = RefCell::new(SimBus::frx());
+    assert!(matches!(
+        Frx::attach(dev(&bus), dev(&bus), dev(&bus)),
+        Err(frx::Error::NotConfigured(_))
+    ));
+}
@@ -0,0 +1,20 @@
+//! The simulated bus itself
+
+use embedded_hal::i2c::{ErrorKind, I2c, NoAcknowledgeSource};
+use rfof::bus::{sim::SimError, SimBus};
+
+const NACK: ErrorKind = ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address);
+
+#[test]
+fn sim_errors_carry_their_kind() {
+    let mut bus = SimBus::ftx();
+    bus.fail_next(1, ErrorKind::ArbitrationLoss);
+    let mut byte = [0];
+    assert_eq!(
+        bus.read(0x48, &mut byte),
+        Err(SimError(ErrorKind::ArbitrationLoss))
+    );
+    assert_eq!(bus.read(0x48, &mut byte), Ok(()));
+    assert_eq!(bus.read(0x33, &mut byte), Err(SimError(NACK)));
+    assert_eq!(bus.transactions, 3);
+}
@@ -0,0 +1,10 @@
+//! Helpers shared by the integration tests
+
+#![allow(dead_code)]
+
+pub fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "{actual} is not within {tolerance} of {expected}"
+    );
+}
@@ -0,0 +1,58 @@
+//! Building modules on each kind of shared bus
+
+use rfof::{
+    bus::{SimBus, SimMux, Tca9548a},
+    modules::{frx::Frx, ftx::Ftx},
+};
+use std::cell::RefCell;
+
+mod common;
+use common::assert_close;
+
+#[test]
+fn modules_build_on_a_mutex() {
+    let bus = std::sync::Mutex::new(SimBus::ftx());
+    let mut ftx = Ftx::new_mutex(&bus);
+    ftx.init().unwrap();
+    ftx.set_ld_current(20.0).unwrap();
+    assert_close(bus.lock().unwrap().ld_current(), 20.0, 0.2);
+
+    let bus = std::sync::Mutex::new(SimBus::frx());
+    let mut frx = Frx::new_mutex(&bus);
+    frx.init().unwrap();
+    assert!(frx.telemetry().is_complete());
+}
+
+#[test]
+fn modules_build_on_a_critical_section() {
+    let bus = critical_section::Mutex::new(RefCell::new(SimBus::ftx()));
+    let mut ftx = Ftx::new_critical_section(&bus);
+    ftx.init().unwrap();
+    ftx.atten.set_db(2.5).unwrap();
+    critical_section::with(|cs| {
+        assert_eq!(bus.borrow_ref(cs).tca6408a.attenuation_db(), 2.5);
+    });
+
+    let bus = critical_section::Mutex::new(RefCell::new(SimBus::frx()));
+    let mut frx = Frx::new_critical_section(&bus);
+    frx.init().unwrap();
+    assert!(frx.telemetry().is_complete());
+}
+
+#[test]
+fn shared_handles_attach_to_running_modules() {
+    let mux = Tca9548a::new(
+        SimMux::new(0)
+            .with_channel(0, SimBus::ftx())
+            .with_channel(1, SimBus::frx()),
+        0,
+    );
+    Ftx::new_shared(mux.channel(0).unwrap()).init().unwrap();
+    assert!(Frx::attach_shared(mux.channel(1).unwrap()).is_err());
+    Frx::new_shared(mux.channel(1).unwrap()).init().unwrap();
+
+    let mut ftx = Ftx::attach_shared(mux.channel(0).unwrap()).unwrap();
+    let mut frx = Frx::attach_shared(mux.channel(1).unwrap()).unwrap();
+    assert_close(ftx.temp.temp().unwrap(), 25.0, 0.01);
+    assert_close(frx.temp.temp().unwrap(), 25.0, 0.01);
+}
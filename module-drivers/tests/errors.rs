@@ -0,0 +1,65 @@
+//! Bus errors carry the chip, address and operation up through the module errors
+
+use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+use embedded_hal_bus::i2c::RefCellDevice;
+use rfof::{
+    bus::SimBus,
+    modules::{
+        frx::{self, Frx},
+        ftx::{self, Ftx},
+        probe::Chip,
+        safety::Trip,
+    },
+    peripherals::temp::{self, TemperataureSensor},
+};
+use std::{cell::RefCell, error::Error};
+
+#[test]
+fn bus_errors_name_the_chip() {
+    let bus = RefCell::new(SimBus::ftx());
+    bus.borrow_mut().remove(Chip::Tmp117);
+    let mut sensor = TemperataureSensor::new(RefCellDevice::new(&bus), 0x48);
+    let Err(temp::Error::I2c(e)) = sensor.temp() else {
+        panic!("expected a bus error");
+    };
+    assert_eq!(e.chip, Chip::Tmp117);
+    assert_eq!(e.addr, 0x48);
+    assert_eq!(
+        e.kind(),
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)
+    );
+    let message = e.to_string();
+    assert!(message.contains("TMP117") && message.contains("TEMP_RESULT"));
+}
+
+#[test]
+fn module_errors_keep_the_bus_context() {
+    let bus = RefCell::new(SimBus::ftx());
+    bus.borrow_mut().remove(Chip::Tca6408a);
+    let err = Ftx::new_refcell(&bus).init().unwrap_err();
+    assert!(matches!(err, ftx::Error::Atten(_)));
+    let message = err.to_string();
+    assert!(
+        message.contains("TCA6408A") && message.contains("0x20"),
+        "{message}"
+    );
+    // The bus error is the source, with the same context
+    let source = err.source().expect("a bus error source");
+    assert_eq!(source.to_string(), message);
+
+    let bus = RefCell::new(SimBus::frx());
+    bus.borrow_mut().remove(Chip::Tla2528);
+    let err = Frx::new_refcell(&bus).init().unwrap_err();
+    assert!(matches!(err, frx::Error::Adc(_)));
+    assert!(err.to_string().contains("TLA2528"));
+}
+
+#[test]
+fn module_errors_without_a_bus_cause_have_no_source() {
+    let err = ftx::Error::<ErrorKind>::Unsafe(Trip::Temperature);
+    assert!(err.source().is_none());
+    assert_eq!(
+        err.to_string(),
+        "refused unsafe laser setpoint: board temperature above limit"
+    );
+}
@@ -0,0 +1,240 @@
+//! FTX laser current control: regulation, calibration and the safety policy
+
+use embedded_hal::i2c::{ErrorType, I2c, Operation};
+use embedded_hal_bus::i2c::RefCellDevice;
+use rfof::{
+    bus::{
+        sim::{SimDelay, SimError},
+        SimBus,
+    },
+    modules::{
+        ftx::{self, Ftx},
+        safety::{Action, Trip},
+    },
+    peripherals::digipot::{Calibration, Digipot},
+};
+use std::cell::{Cell, RefCell};
+
+mod common;
+use common::assert_close;
+
+#[test]
+fn regulation_follows_the_real_current() {
+    let bus = RefCell::new(SimBus::ftx());
+    // Weaker current source than the nominal mapping assumes
+    bus.borrow_mut().board.ld_full_scale = 45.0;
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    let mut delay = SimDelay::default();
+
+    let reg = ftx.regulate_ld_current(20.0, 0.2, &mut delay).unwrap();
+    assert!(reg.converged, "{reg:?}");
+    assert!(reg.iterations > 1);
+    assert_close(reg.current, 20.0, 0.2);
+    assert_close(bus.borrow().ld_current(), 20.0, 0.2);
+    assert_eq!(bus.borrow().cat5171.wiper, reg.word);
+
+    // And a stronger one
+    bus.borrow_mut().board.ld_full_scale = 60.0;
+    let reg = ftx.regulate_ld_current(20.0, 0.2, &mut delay).unwrap();
+    assert!(reg.converged, "{reg:?}");
+    assert_close(bus.borrow().ld_current(), 20.0, 0.2);
+}
+
+#[test]
+fn regulation_gives_up_at_the_end_of_the_wiper() {
+    let bus = RefCell::new(SimBus::ftx());
+    // Can't reach the target at all
+    bus.borrow_mut().board.ld_full_scale = 15.0;
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+
+    let reg = ftx
+        .regulate_ld_current(20.0, 0.2, &mut SimDelay::default())
+        .unwrap();
+    assert!(!reg.converged);
+    assert_eq!(reg.word, 255);
+    assert_close(reg.current, 15.0, 0.1);
+}
+
+#[test]
+fn calibration_lookup_follows_the_curve() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut pot = Digipot::new(RefCellDevice::new(&bus), false);
+    let curve: Vec<f32> = (0..256).map(|word| word as f32 * 0.15).collect();
+    pot.set_calibration(Calibration::new(&curve).unwrap());
+    pot.set(15.0).unwrap();
+    assert_eq!(bus.borrow().cat5171.wiper, 100);
+    assert_close(pot.current(200), 30.0, 1e-3);
+    pot.clear_calibration();
+    assert!(pot.calibration().is_none());
+
+    // A short curve carries on along its last step, and is never looked up past its end
+    let steep: Vec<f32> = (0..101).map(|word| word as f32 * 0.4).collect();
+    let cal = Calibration::new(&steep).unwrap();
+    assert_eq!(cal.points().len(), 101);
+    assert_close(cal.current(150), 60.0, 1e-3);
+    assert_eq!(cal.word(50.0), 100);
+    // Never flatter than the nominal mapping
+    let flat = Calibration::new(&[5.0, 5.0]).unwrap();
+    assert_close(flat.current(2), 5.0 + 50.0 / 255.0, 1e-3);
+
+    assert!(Calibration::new(&[]).is_none());
+    assert!(Calibration::new(&[0.0, f32::INFINITY]).is_none());
+    assert!(Calibration::new(&[0.0; 257]).is_none());
+}
+
+#[test]
+fn calibration_follows_the_real_current() {
+    let bus = RefCell::new(SimBus::ftx());
+    // Weaker current source than the nominal mapping assumes
+    bus.borrow_mut().board.ld_full_scale = 45.0;
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.set_ld_raw(77).unwrap();
+
+    let cal = ftx.calibrate_digipot(&mut SimDelay::default()).unwrap();
+    assert_eq!(cal.points().len(), 256);
+    assert_close(cal.current(255), 45.0, 0.05);
+    // The wiper is put back
+    assert_eq!(bus.borrow().cat5171.wiper, 77);
+    ftx.set_ld_current(30.0).unwrap();
+    assert_close(bus.borrow().ld_current(), 30.0, 0.2);
+}
+
+/// The simulated bus, keeping track of the highest laser current after any transaction
+struct PeakLd<'a> {
+    bus: &'a RefCell<SimBus>,
+    peak: &'a Cell<f32>,
+}
+
+impl ErrorType for PeakLd<'_> {
+    type Error = SimError;
+}
+
+impl I2c for PeakLd<'_> {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut bus = self.bus.borrow_mut();
+        let res = bus.transaction(address, operations);
+        self.peak.set(self.peak.get().max(bus.ld_current()));
+        res
+    }
+}
+
+#[test]
+fn calibration_stops_before_the_limit() {
+    let bus = RefCell::new(SimBus::ftx());
+    // Stronger current source than the nominal mapping assumes
+    bus.borrow_mut().board.ld_full_scale = 80.0;
+    let peak = Cell::new(0.0);
+    let dev = || PeakLd {
+        bus: &bus,
+        peak: &peak,
+    };
+    let mut ftx = Ftx::new(dev(), dev(), dev(), dev());
+    ftx.init().unwrap();
+    ftx.set_ld_raw(0).unwrap();
+    peak.set(0.0);
+
+    let cal = ftx.calibrate_digipot(&mut SimDelay::default()).unwrap();
+    assert!(peak.get() <= ftx.safety.max_ld_current, "{}", peak.get());
+    assert!(cal.points().len() < 256);
+    assert!(cal.points().iter().all(|p| *p <= ftx.safety.max_ld_current));
+    // Past the end is over the limit, so the safety policy refuses it
+    assert!(cal.current(255).is_finite() && cal.current(255) > 50.0);
+    assert!(matches!(
+        ftx.set_ld_raw(255),
+        Err(ftx::Error::Unsafe(Trip::LdCurrent))
+    ));
+}
+
+#[test]
+fn calibration_checks_every_step() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.set_ld_raw(10).unwrap();
+    bus.borrow_mut().tmp117.temp_c = 75.0;
+
+    assert!(matches!(
+        ftx.calibrate_digipot(&mut SimDelay::default()),
+        Err(ftx::Error::Unsafe(Trip::Temperature))
+    ));
+    // Stuck at the first step above off, not back up to where it was
+    assert!(bus.borrow().cat5171.wiper <= 10);
+}
+
+#[test]
+fn laser_setpoints_go_through_the_safety_policy() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.set_ld_current(30.0).unwrap();
+    assert_close(bus.borrow().ld_current(), 30.0, 0.2);
+    assert!(matches!(
+        ftx.set_ld_current(55.0),
+        Err(ftx::Error::Unsafe(Trip::LdCurrent))
+    ));
+
+    bus.borrow_mut().tmp117.temp_c = 75.0;
+    assert!(matches!(
+        ftx.set_ld_raw(200),
+        Err(ftx::Error::Unsafe(Trip::Temperature))
+    ));
+    // Turning it off is always allowed
+    ftx.set_ld_current(0.0).unwrap();
+    assert_eq!(bus.borrow().ld_current(), 0.0);
+
+    bus.borrow_mut().tmp117.temp_c = 25.0;
+    bus.borrow_mut().board.vdd = 4.2;
+    assert!(matches!(
+        ftx.set_ld_current(10.0),
+        Err(ftx::Error::Unsafe(Trip::Vdd))
+    ));
+}
+
+#[test]
+fn poll_safety_cuts_back_and_shuts_down() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.set_ld_current(30.0).unwrap();
+    let status = ftx.poll_safety().unwrap();
+    assert_eq!((status.trip, status.action), (None, Action::None));
+
+    bus.borrow_mut().tmp117.temp_c = 75.0;
+    let status = ftx.poll_safety().unwrap();
+    assert_eq!(status.trip, Some(Trip::Temperature));
+    assert_eq!(status.action, Action::CutBack);
+    assert_close(bus.borrow().ld_current(), 25.0, 0.3);
+
+    bus.borrow_mut().tmp117.temp_c = 85.0;
+    assert_eq!(ftx.poll_safety().unwrap().action, Action::Shutdown);
+    assert_eq!(bus.borrow().cat5171.wiper, 0);
+}
+
+#[test]
+fn lowering_the_laser_is_allowed_while_tripped() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.set_ld_current(30.0).unwrap();
+    let word = ftx.ld_word().unwrap();
+
+    bus.borrow_mut().tmp117.temp_c = 75.0;
+    ftx.set_ld_current(20.0).unwrap();
+    assert_close(bus.borrow().ld_current(), 20.0, 0.2);
+    // Lowering the raw word too, but never back up
+    ftx.set_ld_raw(word / 4).unwrap();
+    assert!(matches!(
+        ftx.set_ld_raw(word / 2),
+        Err(ftx::Error::Unsafe(Trip::Temperature))
+    ));
+    assert_eq!(bus.borrow().cat5171.wiper, word / 4);
+    ftx.ld_off().unwrap();
+    assert_eq!(bus.borrow().ld_current(), 0.0);
+}
@@ -0,0 +1,148 @@
+//! End-to-end links, and the FRX AGC loop
+
+use rfof::{
+    bus::SimBus,
+    modules::{
+        agc::{Agc, AgcConfig},
+        frx::Frx,
+        ftx::Ftx,
+        link::{self, Link},
+        probe::Chip,
+    },
+};
+use std::{cell::RefCell, time::Duration};
+
+mod common;
+use common::assert_close;
+
+const SECOND: Duration = Duration::from_secs(1);
+
+#[test]
+fn link_splits_attenuation_and_measures_loss() {
+    let tx = RefCell::new(SimBus::ftx());
+    let rx = RefCell::new(SimBus::frx());
+    let mut link = Link::new(Ftx::new_refcell(&tx), Frx::new_refcell(&rx));
+    link.init().unwrap();
+
+    assert_eq!(link.set_total_atten(10.25).unwrap(), (5.0, 5.25));
+    assert_eq!(link.total_atten().unwrap(), 10.25);
+    assert!(matches!(
+        link.set_total_atten(64.0),
+        Err(link::Error::OutOfRange)
+    ));
+    rx.borrow_mut().board.rf_input = 0.0;
+    assert_close(link.rf_gain().unwrap(), 10.0 - 5.25 + 5.0, 0.2);
+
+    assert!(matches!(link.optical_loss(), Err(link::Error::NoReference)));
+    link.capture_loss_reference().unwrap();
+    assert_close(link.optical_loss().unwrap(), 0.0, 0.01);
+    rx.borrow_mut().board.rx_pd_current = 1.0;
+    assert_close(link.optical_loss().unwrap(), 3.01, 0.05);
+
+    rx.borrow_mut().remove(Chip::Tla2528);
+    assert!(matches!(link.rf_gain(), Err(link::Error::Frx(_))));
+}
+
+#[test]
+fn total_atten_rounds_to_the_nearest_step() {
+    let tx = RefCell::new(SimBus::ftx());
+    let rx = RefCell::new(SimBus::frx());
+    let mut link = Link::new(Ftx::new_refcell(&tx), Frx::new_refcell(&rx));
+    link.init().unwrap();
+
+    assert_eq!(link.set_total_atten(10.2).unwrap(), (5.0, 5.25));
+    assert_eq!(link.set_total_atten(10.1).unwrap(), (5.0, 5.0));
+    // The top of the range still fits both attenuators
+    assert_eq!(link.set_total_atten(63.5).unwrap(), (31.75, 31.75));
+    assert_eq!(link.total_atten().unwrap(), 63.5);
+}
+
+#[test]
+fn loss_needs_light_at_both_ends() {
+    let tx = RefCell::new(SimBus::ftx());
+    let rx = RefCell::new(SimBus::frx());
+    let mut link = Link::new(Ftx::new_refcell(&tx), Frx::new_refcell(&rx));
+    link.init().unwrap();
+    link.capture_loss_reference().unwrap();
+
+    // The laser is off, nothing launched
+    link.ftx.ld_off().unwrap();
+    assert!(matches!(link.pd_ratio(), Err(link::Error::NoLight)));
+    assert!(matches!(link.optical_loss(), Err(link::Error::NoLight)));
+
+    // A broken fiber, nothing received
+    link.ftx.set_ld_current(25.0).unwrap();
+    rx.borrow_mut().board.rx_pd_current = 0.0;
+    assert!(matches!(link.optical_loss(), Err(link::Error::NoLight)));
+    assert!(matches!(
+        link.capture_loss_reference(),
+        Err(link::Error::NoLight)
+    ));
+    assert!(link.loss_reference.is_some());
+}
+
+#[test]
+fn agc_settles_on_the_target() {
+    let bus = RefCell::new(SimBus::frx());
+    let mut frx = Frx::new_refcell(&bus);
+    frx.init().unwrap();
+    let mut agc = Agc::new(AgcConfig::default());
+
+    let mut updates = 0;
+    while agc.update(&mut frx, SECOND).unwrap().is_some() {
+        updates += 1;
+        assert!(updates < 32, "AGC never settled");
+    }
+    // 10 dB too hot, at most 1 dB/s
+    assert_eq!(updates, 10);
+    assert_eq!(agc.log().count(), 10);
+    assert_close(bus.borrow().rf_power(), -20.0, 0.5);
+
+    bus.borrow_mut().board.rf_input = -15.0;
+    agc.freeze(true);
+    assert!(agc.update(&mut frx, SECOND).unwrap().is_none());
+    agc.freeze(false);
+    let adjustment = agc.update(&mut frx, SECOND).unwrap().unwrap();
+    assert!(adjustment.to_db < adjustment.from_db);
+
+    // Pinned at zero attenuation
+    bus.borrow_mut().board.rf_input = -40.0;
+    while agc.update(&mut frx, SECOND).unwrap().is_some() {}
+    assert_eq!(bus.borrow().tca6408a.attenuation_db(), 0.0);
+}
+
+#[test]
+fn agc_without_a_valid_rate_moves_one_step_per_update() {
+    let bus = RefCell::new(SimBus::frx());
+    let mut frx = Frx::new_refcell(&bus);
+    frx.init().unwrap();
+    for max_rate_db_per_s in [0.0, -1.0, f32::NAN] {
+        frx.atten.set_db(0.0).unwrap();
+        let mut agc = Agc::new(AgcConfig {
+            max_rate_db_per_s,
+            ..Default::default()
+        });
+        let adjustment = agc.update(&mut frx, SECOND).unwrap().unwrap();
+        assert_eq!(
+            adjustment.to_db - adjustment.from_db,
+            0.25,
+            "{max_rate_db_per_s}"
+        );
+    }
+}
+
+#[test]
+fn agc_rate_does_not_depend_on_how_often_it_updates() {
+    let bus = RefCell::new(SimBus::frx());
+    let mut frx = Frx::new_refcell(&bus);
+    frx.init().unwrap();
+    let mut agc = Agc::new(AgcConfig::default());
+
+    // 5 s of updates every 100 ms, each one allowing less than a step on its own
+    for _ in 0..50 {
+        agc.update(&mut frx, Duration::from_millis(100)).unwrap();
+    }
+    let moved = bus.borrow().tca6408a.attenuation_db();
+    assert!((4.75..=5.0).contains(&moved), "{moved}");
+    assert!(agc.log().all(|a| a.to_db - a.from_db == 0.25));
+}
@@ -0,0 +1,78 @@
+//! Several modules on one bus through a TCA9548A
+
+use embedded_hal_bus::i2c::RefCellDevice;
+use rfof::{
+    bus::{mux::CHANNELS, SimBus, SimMux, Tca9548a},
+    modules::{
+        frx::Frx,
+        ftx::{self, Ftx},
+        probe::{probe, ModuleKind},
+    },
+};
+use std::cell::RefCell;
+
+#[test]
+fn mux_keeps_modules_on_the_same_addresses_apart() {
+    let sim = SimMux::new(0)
+        .with_channel(0, SimBus::ftx())
+        .with_channel(3, SimBus::frx());
+    let mux = Tca9548a::new(sim, 0);
+    assert!(mux.channel(CHANNELS).is_none());
+
+    let [ch0, _, _, ch3, ..] = mux.channels();
+    assert_eq!(probe(&mut { ch0 }).unwrap().kind(), ModuleKind::Ftx);
+    assert_eq!(probe(&mut { ch3 }).unwrap().kind(), ModuleKind::Frx);
+    assert_eq!(mux.connected().unwrap(), 1 << 3);
+
+    let mut ftx = Ftx::new_shared(ch0);
+    let mut frx = Frx::new_shared(ch3);
+    ftx.init().unwrap();
+    frx.init().unwrap();
+    ftx.atten.set_db(1.0).unwrap();
+    frx.atten.set_db(2.0).unwrap();
+    assert_ne!(ftx.temp.uid().unwrap(), frx.temp.uid().unwrap());
+    assert_eq!(ftx.atten.get_db().unwrap(), 1.0);
+    assert_eq!(frx.atten.get_db().unwrap(), 2.0);
+
+    // Disconnecting forgets the selection, so the next transaction selects again
+    mux.disconnect().unwrap();
+    assert_eq!(mux.connected().unwrap(), 0);
+    assert!(ftx.temp.temp().is_ok());
+    assert_eq!(mux.connected().unwrap(), 1 << 0);
+
+    let sim = mux.into_inner();
+    let tx = sim.channels[0].as_ref().unwrap();
+    let rx = sim.channels[3].as_ref().unwrap();
+    assert_eq!(tx.tca6408a.attenuation_db(), 1.0);
+    assert_eq!(rx.tca6408a.attenuation_db(), 2.0);
+}
+
+#[test]
+fn mux_without_a_channel_nacks() {
+    let mux = Tca9548a::new(SimMux::new(1).with_channel(5, SimBus::ftx()), 1);
+    // Channel 2 is empty
+    let mut ftx = Ftx::new_shared(mux.channel(2).unwrap());
+    assert!(matches!(ftx.init(), Err(ftx::Error::Atten(_))));
+    mux.invalidate();
+    let mut ftx = Ftx::new_shared(mux.channel(5).unwrap());
+    ftx.init().unwrap();
+    assert_eq!(mux.connected().unwrap(), 1 << 5);
+}
+
+#[test]
+fn mux_selects_again_after_being_switched_behind_its_back() {
+    let sim = RefCell::new(SimMux::new(2).with_channel(4, SimBus::ftx()));
+    let mux = Tca9548a::new(RefCellDevice::new(&sim), 2);
+    let channel = mux.channel(4).unwrap();
+    assert_eq!(channel.index(), 4);
+    let mut ftx = Ftx::new_shared(channel);
+    ftx.init().unwrap();
+    assert_eq!(sim.borrow().control, 1 << 4);
+
+    // Like a reset of the mux, which still thinks channel 4 is selected
+    sim.borrow_mut().control = 0;
+    assert!(ftx.temp.temp().is_err());
+    mux.invalidate();
+    assert!(ftx.temp.temp().is_ok());
+    assert_eq!(sim.borrow().control, 1 << 4);
+}
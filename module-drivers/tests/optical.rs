@@ -0,0 +1,58 @@
+//! Optical power estimates from the photodiode calibrations
+
+use rfof::{
+    bus::SimBus,
+    modules::{
+        frx::{self, Frx},
+        ftx::{self, Ftx},
+        optical::{mw_to_dbm, OpticalCalibration},
+    },
+};
+use std::cell::RefCell;
+
+mod common;
+use common::assert_close;
+
+#[test]
+fn optical_power_needs_this_modules_calibration() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    assert!(matches!(ftx.optical_power(), Err(ftx::Error::Uncalibrated)));
+    let uid = bus.borrow().tmp117.uid();
+    assert!(matches!(
+        ftx.set_optical_calibration(OpticalCalibration::new(uid + 1, 0.1, 1310.0)),
+        Err(ftx::Error::WrongModule(_))
+    ));
+    ftx.set_optical_calibration(OpticalCalibration::new(uid, 0.1, 1310.0))
+        .unwrap();
+    let expected = mw_to_dbm(bus.borrow().pd_current() / 1000.0 / 0.1);
+    assert_close(ftx.optical_power().unwrap(), expected, 0.05);
+
+    let bus = RefCell::new(SimBus::frx());
+    let mut frx = Frx::new_refcell(&bus);
+    frx.init().unwrap();
+    let uid = bus.borrow().tmp117.uid();
+    frx.set_optical_calibration(OpticalCalibration::new(uid, 0.8, 1310.0))
+        .unwrap();
+    assert_close(frx.optical_power().unwrap(), mw_to_dbm(2.0 / 0.8), 0.05);
+    assert!(matches!(
+        frx.set_optical_calibration(OpticalCalibration::new(0, 0.8, 1310.0)),
+        Err(frx::Error::WrongModule(0))
+    ));
+}
+
+#[test]
+fn calibration_converts_currents_to_power() {
+    assert_close(mw_to_dbm(1.0), 0.0, 1e-6);
+    assert_close(mw_to_dbm(0.001), -30.0, 1e-4);
+
+    let cal = OpticalCalibration::new(1, 0.8, 1310.0);
+    assert_close(cal.power_mw(0.4), 0.5, 1e-6);
+    assert_close(cal.power_dbm(0.8), 0.0, 1e-5);
+
+    // A perfect photodiode at 1310 nm gives about 1.06 A/W
+    let ideal = OpticalCalibration::from_quantum_efficiency(1, 1.0, 1310.0);
+    assert_close(ideal.responsivity, 1.0566, 1e-3);
+    assert_eq!(ideal.wavelength_nm, 1310.0);
+}
@@ -0,0 +1,107 @@
+//! The chip drivers against the register models of the simulated bus
+
+use embedded_hal_bus::i2c::RefCellDevice;
+use rfof::{
+    bus::SimBus,
+    peripherals::{
+        adc,
+        atten::{self, Attenuation, Attenuator},
+        digipot::{self, Digipot},
+        temp::{TemperataureSensor, SCALE_C},
+    },
+};
+use std::cell::RefCell;
+
+mod common;
+use common::assert_close;
+
+fn sensor(bus: &RefCell<SimBus>) -> TemperataureSensor<RefCellDevice<'_, SimBus>> {
+    let mut sensor = TemperataureSensor::new(RefCellDevice::new(bus), 0x48);
+    sensor.init().unwrap();
+    sensor
+}
+
+#[test]
+fn temp_reads_uid_and_temperature() {
+    let bus = RefCell::new(SimBus::ftx().with_uid(0x1234_5678_9ABC));
+    let mut sensor = sensor(&bus);
+    assert_eq!(sensor.device_id().unwrap(), 0x117);
+    assert_eq!(sensor.uid().unwrap(), 0x1234_5678_9ABC);
+    assert_close(sensor.temp().unwrap(), 25.0, SCALE_C);
+    bus.borrow_mut().tmp117.temp_c = -12.5;
+    assert_close(sensor.temp().unwrap(), -12.5, SCALE_C);
+}
+
+#[test]
+fn ftx_adc_reads_the_board() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut adc = adc::ftx::Adc::new(RefCellDevice::new(&bus));
+    assert!(!adc.is_initialized().unwrap());
+    adc.init().unwrap();
+    assert!(adc.is_initialized().unwrap());
+
+    let sim = bus.borrow().clone();
+    assert_close(adc.digital_voltage().unwrap(), 5.0, 0.01);
+    assert_close(adc.analog_voltage().unwrap(), 5.0, 0.01);
+    assert_close(adc.ld_current().unwrap(), sim.ld_current(), 0.05);
+    assert_close(adc.pd_current().unwrap(), sim.pd_current(), 1.0);
+    assert_close(adc.rf_power().unwrap(), sim.rf_power(), 0.1);
+
+    assert!(!adc.lna_enabled().unwrap());
+    assert_close(adc.lna_current().unwrap(), 0.0, 0.05);
+    adc.enable_lna(true).unwrap();
+    assert!(adc.lna_enabled().unwrap());
+    assert!(bus.borrow().lna_enabled());
+    assert_close(adc.lna_current().unwrap(), 60.0, 0.1);
+    assert_close(adc.lna_voltage().unwrap(), 5.0, 0.05);
+    adc.enable_lna(false).unwrap();
+    assert!(!bus.borrow().lna_enabled());
+}
+
+#[test]
+fn frx_adc_reads_the_board() {
+    let bus = RefCell::new(SimBus::frx());
+    let mut adc = adc::frx::Adc::new(RefCellDevice::new(&bus));
+    assert!(adc.is_initialized().unwrap());
+    adc.init().unwrap();
+    assert!(adc.is_initialized().unwrap());
+    assert_close(adc.pd_current().unwrap(), 2.0, 0.01);
+    bus.borrow_mut().board.rf_input = -5.0;
+    let expected = bus.borrow().rf_power();
+    assert_close(adc.rf_power().unwrap(), expected, 0.1);
+}
+
+#[test]
+fn atten_sets_the_latch() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut atten = Attenuator::new(RefCellDevice::new(&bus), false);
+    assert!(!atten.is_configured().unwrap());
+    assert_eq!(bus.borrow().tca6408a.attenuation_db(), Attenuation::MAX_DB);
+
+    atten.init().unwrap();
+    assert!(atten.is_configured().unwrap());
+    assert_eq!(atten.get().unwrap(), Attenuation::_0);
+    assert_eq!(bus.borrow().tca6408a.attenuation_db(), 0.0);
+
+    atten.set_db(12.3).unwrap();
+    assert_eq!(atten.get_db().unwrap(), 12.25);
+    assert_eq!(bus.borrow().tca6408a.attenuation_db(), 12.25);
+    assert!(matches!(atten.set_db(40.0), Err(atten::Error::OutOfRange)));
+    assert_eq!(Attenuation::from_db(31.75), Some(Attenuation::_31_75));
+    assert_eq!(Attenuation::from_db(-1.0), None);
+}
+
+#[test]
+fn digipot_sets_the_wiper() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut pot = Digipot::new(RefCellDevice::new(&bus), false);
+    // Powers up at midscale
+    assert_eq!(pot.get_raw().unwrap(), 0x80);
+
+    pot.set_raw(42).unwrap();
+    assert_eq!(bus.borrow().cat5171.wiper, 42);
+    pot.set(25.0).unwrap();
+    assert_eq!(pot.get_raw().unwrap(), 127);
+    assert_close(pot.get().unwrap(), 24.9, 0.1);
+    assert!(matches!(pot.set(60.0), Err(digipot::Error::OutOfRange)));
+}
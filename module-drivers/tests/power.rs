@@ -0,0 +1,156 @@
+//! FTX power-up and power-down sequencing
+
+use rfof::{
+    bus::{sim::SimDelay, SimBus},
+    modules::{
+        ftx::{self, Ftx},
+        power::{PowerConfig, PowerStep},
+    },
+};
+use std::cell::RefCell;
+
+mod common;
+use common::assert_close;
+
+#[test]
+fn power_sequence_brings_everything_up_and_down() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.set_ld_current(0.0).unwrap();
+    let config = PowerConfig::default();
+    let mut delay = SimDelay::default();
+
+    let report = ftx.power_up(&config, &mut delay).unwrap();
+    assert_close(report.ld_current, 25.0, 0.2);
+    assert_close(report.lna_current, 60.0, 0.1);
+    assert!(bus.borrow().lna_enabled());
+    assert!(delay.elapsed_ns > 0);
+
+    ftx.power_down(&config, &mut delay).unwrap();
+    assert_eq!(bus.borrow().ld_current(), 0.0);
+    assert!(!bus.borrow().lna_enabled());
+}
+
+#[test]
+fn failed_power_up_leaves_everything_off() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    let config = PowerConfig::default();
+    let mut delay = SimDelay::default();
+
+    bus.borrow_mut().board.lna_current = 5.0;
+    assert!(matches!(
+        ftx.power_up(&config, &mut delay),
+        Err(ftx::Error::Sequence(PowerStep::Lna))
+    ));
+    assert_eq!(bus.borrow().ld_current(), 0.0);
+    assert!(!bus.borrow().lna_enabled());
+
+    // A laser that never lights up
+    bus.borrow_mut().board.lna_current = 60.0;
+    bus.borrow_mut().board.slope = 0.0;
+    assert!(matches!(
+        ftx.power_up(&config, &mut delay),
+        Err(ftx::Error::Sequence(PowerStep::Photodiode))
+    ));
+    assert_eq!(bus.borrow().ld_current(), 0.0);
+
+    bus.borrow_mut().board.vdda = 4.0;
+    assert!(matches!(
+        ftx.power_up(&config, &mut delay),
+        Err(ftx::Error::Sequence(PowerStep::Supplies))
+    ));
+}
+
+#[test]
+fn power_up_ramps_from_the_present_setpoint() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.set_ld_current(20.0).unwrap();
+    let config = PowerConfig::default();
+    let mut delay = SimDelay::default();
+
+    ftx.power_up(&config, &mut delay).unwrap();
+    assert_close(bus.borrow().ld_current(), 25.0, 0.2);
+    // The LNA settling plus five 1 mA steps, not 25 of them from zero
+    let ms = config.lna_settle_ms + 5 * config.ramp_delay_ms;
+    assert_eq!(delay.elapsed_ns, ms as u64 * 1_000_000);
+}
+
+#[test]
+fn out_of_range_supplies_leave_everything_alone() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.set_ld_current(10.0).unwrap();
+    ftx.adc.enable_lna(true).unwrap();
+
+    bus.borrow_mut().board.vdda = 4.0;
+    assert!(matches!(
+        ftx.power_up(&PowerConfig::default(), &mut SimDelay::default()),
+        Err(ftx::Error::Sequence(PowerStep::Supplies))
+    ));
+    assert_close(bus.borrow().ld_current(), 10.0, 0.2);
+    assert!(bus.borrow().lna_enabled());
+}
+
+#[test]
+fn invalid_configs_are_refused() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    let writes = bus.borrow().register_writes;
+    let default = PowerConfig::default();
+    for config in [
+        PowerConfig {
+            ramp_step: 0.0,
+            ..default
+        },
+        PowerConfig {
+            ramp_step: -1.0,
+            ..default
+        },
+        PowerConfig {
+            ramp_step: f32::NAN,
+            ..default
+        },
+        PowerConfig {
+            ld_current: f32::INFINITY,
+            ..default
+        },
+    ] {
+        assert!(!config.is_valid());
+        assert!(matches!(
+            ftx.power_up(&config, &mut SimDelay::default()),
+            Err(ftx::Error::InvalidConfig)
+        ));
+    }
+    assert_eq!(bus.borrow().register_writes, writes);
+    assert!(default.is_valid());
+}
+
+#[test]
+fn power_down_always_finishes() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    let default = PowerConfig::default();
+    for ramp_step in [0.0, -1.0, f32::NAN, 1e-9] {
+        ftx.set_ld_current(30.0).unwrap();
+        ftx.adc.enable_lna(true).unwrap();
+        let config = PowerConfig {
+            ramp_step,
+            ..default
+        };
+        let mut delay = SimDelay::default();
+        ftx.power_down(&config, &mut delay).unwrap();
+        assert_eq!(bus.borrow().ld_current(), 0.0);
+        assert!(!bus.borrow().lna_enabled());
+        // At most a thousand ramp steps and the final one to off, then the LNA
+        let ms = 1001 * config.ramp_delay_ms + config.lna_settle_ms;
+        assert!(delay.elapsed_ns <= ms as u64 * 1_000_000);
+    }
+}
@@ -0,0 +1,47 @@
+//! Telling the modules apart by which chips answer
+
+use embedded_hal::i2c::ErrorKind;
+use rfof::{
+    bus::SimBus,
+    modules::probe::{probe, Chip, ModuleKind},
+};
+
+#[test]
+fn probe_tells_the_modules_apart() {
+    let mut ftx = SimBus::ftx();
+    assert_eq!(probe(&mut ftx).unwrap().kind(), ModuleKind::Ftx);
+    let mut frx = SimBus::frx();
+    let report = probe(&mut frx).unwrap();
+    assert_eq!(report.kind(), ModuleKind::Frx);
+    assert_eq!(report.expect(ModuleKind::Ftx), Err(Chip::Cat5171));
+
+    ftx.remove(Chip::Tla2528);
+    let report = probe(&mut ftx).unwrap();
+    assert_eq!(report.kind(), ModuleKind::Unknown);
+    assert_eq!(report.expect(ModuleKind::Ftx), Err(Chip::Tla2528));
+
+    // Anything but a NACK isn't an answer
+    ftx.fail_next(1, ErrorKind::Bus);
+    assert!(probe(&mut ftx).is_err());
+}
+
+#[test]
+fn probe_reports_every_chip() {
+    let mut ftx = SimBus::ftx();
+    let report = probe(&mut ftx).unwrap();
+    for chip in ModuleKind::Ftx.chips() {
+        assert!(report.present(*chip), "{chip}");
+    }
+    assert_eq!(Chip::Tmp117.addr(), 0x48);
+    assert_eq!(Chip::Cat5171.addr(), 0x2C);
+
+    // Nothing at all is not a module
+    let mut empty = SimBus::frx();
+    for chip in ModuleKind::Frx.chips() {
+        empty.remove(*chip);
+    }
+    let report = probe(&mut empty).unwrap();
+    assert_eq!(report.kind(), ModuleKind::Unknown);
+    assert_eq!(report.expect(ModuleKind::Frx), Err(Chip::Tmp117));
+    assert!(report.to_string().starts_with("detected unknown board"));
+}
@@ -0,0 +1,140 @@
+//! The module registry: identification and per-unit calibration
+
+use rfof::{
+    bus::{sim::SimDelay, SimBus},
+    modules::{
+        frx::{self, Frx},
+        ftx::Ftx,
+        probe::ModuleKind,
+        registry::{ModuleEntry, Photodiode, Registry, UnitCalibration, Warning},
+        state::StateFile,
+    },
+};
+use std::cell::RefCell;
+
+#[test]
+fn registry_identifies_and_calibrates_modules() {
+    let tx = RefCell::new(SimBus::ftx());
+    let rx = RefCell::new(SimBus::frx());
+    let tx_uid = tx.borrow().tmp117.uid();
+    let rx_uid = rx.borrow().tmp117.uid();
+    let mut registry = Registry::default();
+    registry.insert(ModuleEntry {
+        uid: tx_uid,
+        serial: "FTX-0001".into(),
+        kind: ModuleKind::Ftx,
+        label: Some("ant-1".into()),
+        calibration: UnitCalibration {
+            optical: Some(Photodiode {
+                responsivity: 0.1,
+                wavelength_nm: 1310.0,
+            }),
+            digipot: Some((0..256).map(|w| w as f32 * 0.1).collect()),
+        },
+    });
+    registry.insert(ModuleEntry {
+        uid: rx_uid,
+        serial: "FTX-0002".into(),
+        // Registered as the wrong kind
+        kind: ModuleKind::Ftx,
+        label: None,
+        calibration: UnitCalibration {
+            optical: Some(Photodiode {
+                responsivity: 0.8,
+                wavelength_nm: 1310.0,
+            }),
+            digipot: None,
+        },
+    });
+    let registry = Registry::from_toml(&registry.to_toml().unwrap()).unwrap();
+
+    let mut ftx = Ftx::new_refcell(&tx);
+    ftx.init().unwrap();
+    let identity = ftx.identify(&registry, Some("ant-1")).unwrap();
+    assert_eq!(identity.serial(), Some("FTX-0001"));
+    assert!(identity.warnings.is_empty(), "{:?}", identity.warnings);
+    assert!(ftx.optical_power().is_ok());
+    ftx.set_ld_current(10.0).unwrap();
+    assert_eq!(tx.borrow().cat5171.wiper, 100);
+
+    let identity = ftx.identify(&registry, Some("ant-2")).unwrap();
+    assert!(matches!(
+        identity.warnings.as_slice(),
+        [Warning::WrongLocation { .. }]
+    ));
+
+    let mut frx = Frx::new_refcell(&rx);
+    frx.init().unwrap();
+    let identity = frx.identify(&registry, None).unwrap();
+    assert!(matches!(
+        identity.warnings.as_slice(),
+        [Warning::WrongKind {
+            found: ModuleKind::Frx,
+            ..
+        }]
+    ));
+    assert!(matches!(frx.optical_power(), Err(frx::Error::Uncalibrated)));
+
+    let unknown = RefCell::new(SimBus::ftx().with_uid(42));
+    let mut ftx = Ftx::new_refcell(&unknown);
+    let identity = ftx.identify(&registry, None).unwrap();
+    assert_eq!(identity.warnings, [Warning::Unknown(42)]);
+    assert!(identity.entry.is_none());
+}
+
+fn ftx_entry(uid: u64, digipot: Vec<f32>) -> ModuleEntry {
+    ModuleEntry {
+        uid,
+        serial: "FTX-0001".into(),
+        kind: ModuleKind::Ftx,
+        label: None,
+        calibration: UnitCalibration {
+            optical: None,
+            digipot: Some(digipot),
+        },
+    }
+}
+
+#[test]
+fn measured_digipot_curves_survive_the_registry() {
+    let bus = RefCell::new(SimBus::ftx());
+    // Stronger current source than the nominal mapping assumes, so the curve stops short
+    bus.borrow_mut().board.ld_full_scale = 80.0;
+    let uid = bus.borrow().tmp117.uid();
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    let cal = ftx.calibrate_digipot(&mut SimDelay::default()).unwrap();
+    assert!(cal.points().len() < 256);
+    ftx.clear_digipot_calibration();
+
+    let mut registry = Registry::default();
+    registry.insert(ftx_entry(uid, cal.points().to_vec()));
+    let from_toml = Registry::from_toml(&registry.to_toml().unwrap()).unwrap();
+    let from_json = Registry::from_json(&registry.to_json().unwrap()).unwrap();
+    assert_eq!(from_toml, registry);
+    assert_eq!(from_json, registry);
+
+    for registry in [from_toml, from_json] {
+        ftx.clear_digipot_calibration();
+        let identity = ftx.identify(&registry, None).unwrap();
+        assert!(identity.warnings.is_empty(), "{:?}", identity.warnings);
+        assert_eq!(ftx.digipot().calibration(), Some(&cal));
+    }
+}
+
+#[test]
+fn bad_digipot_curves_are_ignored() {
+    let bus = RefCell::new(SimBus::ftx());
+    let uid = bus.borrow().tmp117.uid();
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+
+    for curve in [vec![], vec![1.0; 257], vec![1.0, f32::NAN]] {
+        let len = curve.len();
+        let mut registry = Registry::default();
+        registry.insert(ftx_entry(uid, curve));
+        let identity = ftx.identify(&registry, None).unwrap();
+        assert_eq!(identity.warnings, [Warning::BadDigipotCurve(len)]);
+        assert!(ftx.digipot().calibration().is_none());
+    }
+}
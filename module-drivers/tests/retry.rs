@@ -0,0 +1,85 @@
+//! Retrying transient bus failures
+
+use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+use embedded_hal_bus::i2c::RefCellDevice;
+use rfof::{
+    bus::{retry::is_retryable, sim::SimDelay, RetryConfig, RetryI2c, RetryStats, SimBus},
+    modules::ftx::Ftx,
+    peripherals::temp,
+};
+use std::cell::RefCell;
+
+const NACK: ErrorKind = ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address);
+
+#[test]
+fn retry_rides_out_transient_failures() {
+    let sim = RefCell::new(SimBus::ftx());
+    let bus = RefCell::new(RetryI2c::new(RefCellDevice::new(&sim), SimDelay::default()));
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    bus.borrow_mut().reset_stats();
+
+    sim.borrow_mut().fail_next(2, NACK);
+    ftx.temp.temp().unwrap();
+    assert_eq!(
+        bus.borrow().stats(),
+        RetryStats {
+            transactions: 1,
+            retries: 2,
+            recovered: 1,
+            failures: 0,
+        }
+    );
+
+    // More failures than retries
+    sim.borrow_mut().fail_next(10, ErrorKind::ArbitrationLoss);
+    assert!(matches!(ftx.temp.temp(), Err(temp::Error::I2c(_))));
+    assert_eq!(bus.borrow().stats().failures, 1);
+
+    // Not worth retrying, or not safe to
+    for kind in [ErrorKind::Overrun, ErrorKind::Bus] {
+        bus.borrow_mut().reset_stats();
+        sim.borrow_mut().fail_next(1, kind);
+        assert!(ftx.atten.get_db().is_err());
+        assert_eq!(bus.borrow().stats().retries, 0);
+    }
+    let (_, delay) = bus.into_inner().into_inner();
+    assert!(delay.elapsed_ns > 0);
+}
+
+#[test]
+fn only_errors_that_never_reached_the_chip_are_retried() {
+    assert!(is_retryable(NACK));
+    assert!(is_retryable(ErrorKind::NoAcknowledge(
+        NoAcknowledgeSource::Data
+    )));
+    assert!(is_retryable(ErrorKind::ArbitrationLoss));
+    assert!(!is_retryable(ErrorKind::Bus));
+    assert!(!is_retryable(ErrorKind::Overrun));
+    assert!(!is_retryable(ErrorKind::Other));
+}
+
+#[test]
+fn retries_back_off_and_stop_when_configured() {
+    let sim = RefCell::new(SimBus::ftx());
+    let config = RetryConfig {
+        retries: 2,
+        backoff_us: 100,
+        backoff_factor: 3,
+    };
+    let bus = RefCell::new(RetryI2c::with_config(
+        RefCellDevice::new(&sim),
+        SimDelay::default(),
+        config,
+    ));
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+
+    sim.borrow_mut().fail_next(3, NACK);
+    assert!(ftx.temp.temp().is_err());
+    let stats = bus.borrow().stats();
+    assert_eq!((stats.retries, stats.failures), (2, 1));
+    // Waited 100 us, then 300 us
+    let (_, delay) = bus.into_inner().into_inner();
+    assert_eq!(delay.elapsed_ns, 400_000);
+}
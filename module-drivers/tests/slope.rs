@@ -0,0 +1,102 @@
+//! Laser slope-efficiency sweeps and their aging history
+
+use rfof::{
+    bus::{sim::SimDelay, SimBus},
+    modules::{
+        ftx::{self, Ftx},
+        safety::Trip,
+        slope::{self, HistoryError, SweepConfig},
+    },
+};
+use std::cell::RefCell;
+
+mod common;
+use common::assert_close;
+
+#[test]
+fn slope_sweep_finds_threshold_and_efficiency() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.set_ld_raw(77).unwrap();
+    let mut delay = SimDelay::default();
+
+    let record = ftx
+        .measure_slope(&SweepConfig::default(), 1_700_000_000, &mut delay)
+        .unwrap();
+    assert_close(record.threshold_ma, 8.0, 0.3);
+    assert_close(record.slope_ua_per_ma, 12.0, 0.3);
+    assert_eq!(record.uid, bus.borrow().tmp117.uid());
+    assert!(record.slope_w_per_a.is_none());
+    // Nothing above the sweep limit, and the wiper is put back
+    let curve = ftx.sweep_li(&SweepConfig::default(), &mut delay).unwrap();
+    assert!(curve.points().iter().all(|p| p.ld_current <= 40.5));
+    assert_eq!(bus.borrow().cat5171.wiper, 77);
+
+    bus.borrow_mut().board.slope = 0.0;
+    assert!(matches!(
+        ftx.measure_slope(&SweepConfig::default(), 0, &mut delay),
+        Err(ftx::Error::NoLasing)
+    ));
+}
+
+#[test]
+fn slope_history_tracks_aging() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    let mut delay = SimDelay::default();
+    let path = std::env::temp_dir().join(format!("rfof-slope-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let day = 86_400;
+    for (days, threshold) in [(0, 8.0), (10, 9.0), (20, 10.0)] {
+        bus.borrow_mut().board.threshold = threshold;
+        let record = ftx
+            .measure_slope(&SweepConfig::default(), days * day, &mut delay)
+            .unwrap();
+        slope::append_history(&path, &record).unwrap();
+    }
+    let history = slope::load_history(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(history.len(), 3);
+
+    let trend = slope::trend(&history, bus.borrow().tmp117.uid()).unwrap();
+    assert_eq!(trend.records, 3);
+    assert_close(trend.span_days, 20.0, 1e-3);
+    assert_close(trend.threshold_ma_per_day, 0.1, 0.02);
+    assert_close(trend.slope_per_day, 0.0, 0.02);
+    assert!(slope::trend(&history, 0).is_none());
+}
+
+#[test]
+fn slope_history_reports_bad_files() {
+    let dir = std::env::temp_dir().join(format!("rfof-history-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    assert!(matches!(
+        slope::load_history(dir.join("missing.jsonl")),
+        Err(HistoryError::Io(_))
+    ));
+
+    let path = dir.join("garbled.jsonl");
+    std::fs::write(&path, "{\"uid\": 1}\n\nnot json\n").unwrap();
+    let err = slope::load_history(&path).unwrap_err();
+    assert!(matches!(err, HistoryError::Json(_)));
+    assert!(err.to_string().contains("slope history"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn slope_sweep_restores_the_wiper_through_the_safety_policy() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.set_ld_raw(77).unwrap();
+    // Too hot to sweep at all, and the laser isn't turned back up either
+    bus.borrow_mut().tmp117.temp_c = 75.0;
+    assert!(matches!(
+        ftx.sweep_li(&SweepConfig::default(), &mut SimDelay::default()),
+        Err(ftx::Error::Unsafe(Trip::Temperature))
+    ));
+    assert!(bus.borrow().cat5171.wiper < 77);
+}
@@ -0,0 +1,96 @@
+//! Capturing, comparing and restoring module settings
+
+use rfof::{
+    bus::SimBus,
+    modules::{
+        frx::Frx,
+        ftx::{self, Ftx},
+        safety::Trip,
+        state::{FrxState, FtxState, StateFile, StateFileError},
+    },
+};
+use std::cell::RefCell;
+
+#[test]
+fn state_round_trips_through_the_hardware() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.atten.set_db(4.5).unwrap();
+    ftx.adc.enable_lna(true).unwrap();
+    ftx.set_ld_raw(90).unwrap();
+    ftx.temp.set_offset(0.5).unwrap();
+    let saved = ftx.capture_state().unwrap();
+    assert_eq!(saved.ld_word, 90);
+    assert!(ftx.diff_state(&saved).unwrap().is_empty());
+
+    let restored = FtxState::from_toml(&saved.to_toml().unwrap()).unwrap();
+    assert_eq!(restored, saved);
+
+    ftx.init().unwrap();
+    ftx.set_ld_raw(10).unwrap();
+    let diff = ftx.diff_state(&saved).unwrap();
+    assert!(!diff.is_empty());
+    assert!(diff.lna_enabled.is_some() && diff.ld_word.is_some());
+
+    ftx.apply_state(&restored).unwrap();
+    assert!(ftx.diff_state(&saved).unwrap().is_empty());
+    assert_eq!(bus.borrow().tca6408a.attenuation_db(), 4.5);
+    assert!(bus.borrow().lna_enabled());
+}
+
+#[test]
+fn frx_state_round_trips_through_the_hardware() {
+    let bus = RefCell::new(SimBus::frx());
+    let mut frx = Frx::new_refcell(&bus);
+    frx.init().unwrap();
+    frx.atten.set_db(20.0).unwrap();
+    let saved = frx.capture_state().unwrap();
+    frx.init().unwrap();
+    assert!(!frx.diff_state(&saved).unwrap().is_empty());
+    frx.apply_state(&saved).unwrap();
+    assert!(frx.diff_state(&saved).unwrap().is_empty());
+}
+
+#[test]
+fn state_files_pick_their_format_from_the_extension() {
+    let bus = RefCell::new(SimBus::frx());
+    let mut frx = Frx::new_refcell(&bus);
+    frx.init().unwrap();
+    frx.atten.set_db(6.0).unwrap();
+    let saved = frx.capture_state().unwrap();
+
+    let dir = std::env::temp_dir().join(format!("rfof-state-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for name in ["frx.toml", "frx.json"] {
+        let path = dir.join(name);
+        saved.save(&path).unwrap();
+        assert_eq!(FrxState::load(&path).unwrap(), saved);
+    }
+    assert!(matches!(
+        saved.save(dir.join("frx.yaml")),
+        Err(StateFileError::UnknownFormat)
+    ));
+    assert!(matches!(
+        FrxState::load(dir.join("missing.toml")),
+        Err(StateFileError::Io(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn restoring_the_laser_goes_through_the_safety_policy() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.set_ld_raw(120).unwrap();
+    let saved = ftx.capture_state().unwrap();
+
+    ftx.ld_off().unwrap();
+    bus.borrow_mut().tmp117.temp_c = 75.0;
+    assert!(matches!(
+        ftx.apply_state(&saved),
+        Err(ftx::Error::Unsafe(Trip::Temperature))
+    ));
+    assert_eq!(bus.borrow().cat5171.wiper, 0);
+}
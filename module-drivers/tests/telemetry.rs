@@ -0,0 +1,159 @@
+//! Reading the modules through the common trait, telemetry snapshots and their health
+
+use rfof::{
+    bus::SimBus,
+    modules::{
+        frx::Frx,
+        ftx::Ftx,
+        health::{FrxHealthConfig, FtxHealthConfig, Limits, Range, Status},
+        probe::Chip,
+        RfofModule,
+    },
+};
+use std::cell::RefCell;
+
+mod common;
+use common::assert_close;
+
+fn summary<M: RfofModule>(module: &mut M) -> (u64, f32, f32)
+where
+    M::Error: std::fmt::Debug,
+{
+    module.init().unwrap();
+    module.set_atten(3.0).unwrap();
+    (
+        module.uid().unwrap(),
+        module.temp().unwrap(),
+        module.atten().unwrap(),
+    )
+}
+
+#[test]
+fn both_modules_run_through_the_common_trait() {
+    let tx = RefCell::new(SimBus::ftx());
+    let rx = RefCell::new(SimBus::frx());
+    let (uid, temp, atten) = summary(&mut Ftx::new_refcell(&tx));
+    assert_eq!(uid, tx.borrow().tmp117.uid());
+    assert_close(temp, 25.0, 0.01);
+    assert_eq!(atten, 3.0);
+    let (uid, _, _) = summary(&mut Frx::new_refcell(&rx));
+    assert_eq!(uid, rx.borrow().tmp117.uid());
+}
+
+/// RF power (in dBm) and photodiode current (in mA), through the trait alone
+fn power_readings<M: RfofModule>(module: &mut M) -> (f32, f32)
+where
+    M::Error: std::fmt::Debug,
+{
+    (module.rf_power().unwrap(), module.pd_current().unwrap())
+}
+
+#[test]
+fn the_common_trait_reads_the_board() {
+    let tx = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&tx);
+    ftx.init().unwrap();
+    let (rf, pd) = power_readings(&mut ftx);
+    assert_close(rf, tx.borrow().rf_power(), 0.1);
+    // The trait reports the photodiode in mA for both modules
+    assert_close(pd, tx.borrow().pd_current() / 1000.0, 1e-3);
+
+    let rx = RefCell::new(SimBus::frx());
+    let mut frx = Frx::new_refcell(&rx);
+    frx.init().unwrap();
+    let (_, pd) = power_readings(&mut frx);
+    assert_close(pd, 2.0, 0.01);
+    // Neither has a photodiode calibration yet
+    assert!(frx.optical_power().is_err());
+    assert!(ftx.optical_power().is_err());
+}
+
+#[test]
+fn telemetry_keeps_what_it_could_read() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    bus.borrow_mut().remove(Chip::Tmp117);
+    let telem = ftx.telemetry();
+    assert!(!telem.is_complete());
+    assert!(telem.temp_c.is_err() && telem.uid.is_err());
+    assert!(telem.vdd_v.is_ok() && telem.atten_db.is_ok());
+}
+
+#[test]
+fn frx_telemetry_keeps_what_it_could_read() {
+    let bus = RefCell::new(SimBus::frx());
+    let mut frx = Frx::new_refcell(&bus);
+    frx.init().unwrap();
+    let telem = frx.telemetry();
+    assert!(telem.is_complete());
+    assert!(telem.acquisition.is_some());
+
+    bus.borrow_mut().remove(Chip::Tca6408a);
+    let telem = frx.telemetry();
+    assert!(!telem.is_complete());
+    assert!(telem.atten_db.is_err());
+    assert!(telem.temp_c.is_ok() && telem.pd_current_ma.is_ok());
+}
+
+#[test]
+fn ftx_telemetry_is_healthy() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.adc.enable_lna(true).unwrap();
+    let telem = ftx.telemetry();
+    assert!(telem.is_complete());
+    assert_close(*telem.ld_current_ma.as_ref().unwrap(), 25.1, 0.1);
+    let health = FtxHealthConfig::default().evaluate(&telem);
+    assert_eq!(health.overall(), Status::Ok, "{health:?}");
+
+    // A dead LNA is a fault
+    bus.borrow_mut().board.lna_current = 0.0;
+    let health = FtxHealthConfig::default().evaluate(&ftx.telemetry());
+    assert_eq!(health.lna_current, Status::Fault);
+    assert_eq!(health.overall(), Status::Fault);
+}
+
+#[test]
+fn frx_telemetry_is_healthy() {
+    let bus = RefCell::new(SimBus::frx());
+    let mut frx = Frx::new_refcell(&bus);
+    frx.init().unwrap();
+    let telem = frx.telemetry();
+    assert!(telem.is_complete());
+    assert_close(*telem.pd_current_ma.as_ref().unwrap(), 2.0, 0.01);
+    assert_eq!(
+        FrxHealthConfig::default().evaluate(&telem).overall(),
+        Status::Ok
+    );
+
+    bus.borrow_mut().board.rx_pd_current = 0.3;
+    let health = FrxHealthConfig::default().evaluate(&frx.telemetry());
+    assert_eq!(health.pd_current, Status::Warning);
+}
+
+#[test]
+fn limits_grade_readings_by_severity() {
+    let limits = Limits::new(Range::new(4.75, 5.25), Range::new(4.5, 5.5));
+    assert_eq!(limits.status(5.0), Status::Ok);
+    assert_eq!(limits.status(5.25), Status::Ok);
+    assert_eq!(limits.status(4.6), Status::Warning);
+    assert_eq!(limits.status(5.6), Status::Fault);
+    // A channel that couldn't be read is a fault
+    assert_eq!(limits.check(&Err::<f32, ()>(())), Status::Fault);
+    assert!(Status::Fault > Status::Warning && Status::Warning > Status::Ok);
+}
+
+#[test]
+fn unreadable_channels_are_faults() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut ftx = Ftx::new_refcell(&bus);
+    ftx.init().unwrap();
+    ftx.adc.enable_lna(true).unwrap();
+    bus.borrow_mut().remove(Chip::Tmp117);
+    let health = FtxHealthConfig::default().evaluate(&ftx.telemetry());
+    assert_eq!(health.temp, Status::Fault);
+    assert_eq!(health.vdd, Status::Ok);
+    assert_eq!(health.overall(), Status::Fault);
+}
@@ -0,0 +1,244 @@
+//! TMP117 configuration, alerts, offset and EEPROM against the simulated bus
+
+use embedded_hal_bus::i2c::RefCellDevice;
+use rfof::{
+    bus::{sim::SimDelay, SimBus},
+    modules::state::TempState,
+    peripherals::temp::{
+        self, AlertConfig, AlertFlags, AlertMode, AveragingMode, ConversionCycle, ConversionMode,
+        EepromWord, TempConfig, TemperataureSensor, SCALE_C,
+    },
+};
+use std::cell::RefCell;
+
+mod common;
+use common::assert_close;
+
+fn sensor(bus: &RefCell<SimBus>) -> TemperataureSensor<RefCellDevice<'_, SimBus>> {
+    let mut sensor = TemperataureSensor::new(RefCellDevice::new(bus), 0x48);
+    sensor.init().unwrap();
+    sensor
+}
+
+#[test]
+fn temp_config_round_trips() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut sensor = sensor(&bus);
+    assert_eq!(sensor.config().unwrap(), TempConfig::default());
+    assert!(sensor.is_running().unwrap());
+
+    let config = TempConfig {
+        mode: ConversionMode::Shutdown,
+        cycle: ConversionCycle::_1s,
+        avg: AveragingMode::_8,
+    };
+    sensor.configure(config).unwrap();
+    assert_eq!(sensor.config().unwrap(), config);
+    assert!(!sensor.is_running().unwrap());
+}
+
+#[test]
+fn temp_one_shot_converts_once() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut sensor = sensor(&bus);
+    bus.borrow_mut().tmp117.temp_c = 30.0;
+    sensor
+        .configure(TempConfig {
+            mode: ConversionMode::OneShot,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(sensor.config().unwrap().mode, ConversionMode::Shutdown);
+    bus.borrow_mut().tmp117.temp_c = 40.0;
+    assert_close(sensor.temp().unwrap(), 30.0, SCALE_C);
+}
+
+#[test]
+fn temp_limits_and_alert_mode() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut sensor = sensor(&bus);
+    sensor.set_limits(10.0, 40.0).unwrap();
+    assert_eq!(sensor.limits().unwrap(), (10.0, 40.0));
+    assert!(matches!(
+        sensor.set_limits(40.0, 10.0),
+        Err(temp::Error::OutOfRange)
+    ));
+
+    bus.borrow_mut().tmp117.temp_c = 45.0;
+    sensor.temp().unwrap();
+    bus.borrow_mut().tmp117.temp_c = 25.0;
+    sensor.temp().unwrap();
+    // Latched until read
+    let flags = sensor.alert_flags().unwrap();
+    assert!(flags.high && !flags.low);
+    assert!(!sensor.alert_flags().unwrap().high);
+
+    bus.borrow_mut().tmp117.temp_c = 5.0;
+    sensor.temp().unwrap();
+    assert!(sensor.alert_flags().unwrap().low);
+}
+
+#[test]
+fn temp_therm_mode_has_hysteresis() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut sensor = sensor(&bus);
+    sensor.set_limits(10.0, 40.0).unwrap();
+    let config = AlertConfig {
+        mode: AlertMode::Therm,
+        active_high: true,
+        data_ready_pin: false,
+    };
+    sensor.set_alert_config(config).unwrap();
+    assert_eq!(sensor.alert_config().unwrap(), config);
+
+    for (temp, high) in [(45.0, true), (25.0, true), (5.0, false), (25.0, false)] {
+        bus.borrow_mut().tmp117.temp_c = temp;
+        sensor.temp().unwrap();
+        assert_eq!(sensor.alert_flags().unwrap().high, high, "at {temp} C");
+    }
+}
+
+#[test]
+fn temp_alert_flags_survive_configuration_reads() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut sensor = sensor(&bus);
+    sensor.set_limits(10.0, 40.0).unwrap();
+    bus.borrow_mut().tmp117.temp_c = 45.0;
+    sensor.temp().unwrap();
+    bus.borrow_mut().tmp117.temp_c = 25.0;
+    sensor.temp().unwrap();
+
+    // Each of these reads the configuration register, clearing the flags in hardware
+    sensor.config().unwrap();
+    assert!(sensor.is_running().unwrap());
+    sensor.alert_config().unwrap();
+    sensor.set_alert_config(AlertConfig::default()).unwrap();
+    sensor.configure(TempConfig::default()).unwrap();
+    TempState::capture(&mut sensor).unwrap();
+
+    let flags = sensor.alert_flags().unwrap();
+    assert!(flags.high && !flags.low);
+    assert_eq!(sensor.alert_flags().unwrap(), AlertFlags::default());
+
+    // A reset forgets them
+    bus.borrow_mut().tmp117.temp_c = 5.0;
+    sensor.temp().unwrap();
+    sensor.config().unwrap();
+    sensor.init().unwrap();
+    assert_eq!(sensor.alert_flags().unwrap(), AlertFlags::default());
+}
+
+#[test]
+fn temp_offset_lasts_until_reset_unless_persisted() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut sensor = sensor(&bus);
+    sensor.set_offset(1.5).unwrap();
+    assert_eq!(sensor.offset().unwrap(), 1.5);
+    assert_close(sensor.temp().unwrap(), 26.5, SCALE_C);
+    sensor.init().unwrap();
+    assert_eq!(sensor.offset().unwrap(), 0.0);
+
+    sensor.set_offset(-2.0).unwrap();
+    sensor.persist_offset(&mut SimDelay::default()).unwrap();
+    assert!(!bus.borrow().tmp117.is_unlocked());
+    sensor.init().unwrap();
+    assert_eq!(sensor.offset().unwrap(), -2.0);
+    assert!(matches!(
+        sensor.set_offset(1000.0),
+        Err(temp::Error::OutOfRange)
+    ));
+}
+
+#[test]
+fn temp_offset_is_quantized_to_the_lsb() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut sensor = sensor(&bus);
+    sensor.set_offset(-0.5 - SCALE_C / 4.0).unwrap();
+    assert_close(sensor.offset().unwrap(), -0.5, SCALE_C);
+    assert_close(sensor.temp().unwrap(), 24.5, 2.0 * SCALE_C);
+    // Negative temperatures shift the same way
+    bus.borrow_mut().tmp117.temp_c = -20.0;
+    assert_close(sensor.temp().unwrap(), -20.5, 2.0 * SCALE_C);
+}
+
+#[test]
+fn temp_refuses_other_devices() {
+    let bus = RefCell::new(SimBus::ftx());
+    bus.borrow_mut().tmp117.device_id = 0x116;
+    let mut sensor = TemperataureSensor::new(RefCellDevice::new(&bus), 0x48);
+    assert!(matches!(
+        sensor.init(),
+        Err(temp::Error::UnknownDevice(0x116))
+    ));
+    assert!(!sensor.is_running().unwrap());
+}
+
+#[test]
+fn temp_eeprom_words_are_programmed() {
+    let bus = RefCell::new(SimBus::ftx().with_uid(0x1234_5678_9ABC));
+    let mut sensor = sensor(&bus);
+    let mut delay = SimDelay::default();
+    sensor
+        .write_eeprom(EepromWord::Three, 0xBEEF, &mut delay)
+        .unwrap();
+    assert_eq!(sensor.read_eeprom(EepromWord::Three).unwrap(), 0xBEEF);
+    assert_eq!(sensor.read_eeprom(EepromWord::One).unwrap(), 0x1234);
+    assert!(!bus.borrow().tmp117.is_unlocked());
+    sensor.init().unwrap();
+    assert_eq!(sensor.uid().unwrap(), 0x1234_5678_BEEF);
+}
+
+#[test]
+fn temp_eeprom_waits_for_programming() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut sensor = sensor(&bus);
+    let mut delay = SimDelay::default();
+    sensor
+        .write_eeprom(EepromWord::Two, 0x1234, &mut delay)
+        .unwrap();
+    // Polled the busy flag at least once
+    assert!(delay.elapsed_ns >= 1_000_000);
+    assert_eq!(sensor.read_eeprom(EepromWord::Two).unwrap(), 0x1234);
+}
+
+#[test]
+fn temp_fresh_needs_conversions() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut sensor = sensor(&bus);
+    let mut delay = SimDelay::default();
+    assert_close(sensor.temp_fresh(&mut delay).unwrap(), 25.0, SCALE_C);
+    assert!(delay.elapsed_ns > 0);
+
+    sensor
+        .configure(TempConfig {
+            mode: ConversionMode::Shutdown,
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(matches!(
+        sensor.temp_fresh(&mut delay),
+        Err(temp::Error::Timeout)
+    ));
+}
+
+#[test]
+fn temp_fresh_times_out_on_a_stalled_sensor() {
+    let bus = RefCell::new(SimBus::ftx());
+    let mut sensor = sensor(&bus);
+    let mut delay = SimDelay::default();
+    bus.borrow_mut().tmp117.stalled = true;
+    // Still free-running as far as the configuration goes, and the old result is still there
+    assert!(sensor.is_running().unwrap());
+    assert_close(sensor.temp().unwrap(), 25.0, SCALE_C);
+    assert!(matches!(
+        sensor.temp_fresh(&mut delay),
+        Err(temp::Error::Timeout)
+    ));
+    // Gave it a few conversion cycles
+    let cycle_ns = TempConfig::default().cycle_time_ms() * 1e6;
+    assert!(delay.elapsed_ns as f32 >= 2.0 * cycle_ns);
+
+    bus.borrow_mut().tmp117.stalled = false;
+    bus.borrow_mut().tmp117.temp_c = 31.0;
+    assert_close(sensor.temp_fresh(&mut delay).unwrap(), 31.0, SCALE_C);
+}